@@ -1,2 +1,6 @@
 pub mod fd;
 pub mod gen;
+pub mod install;
+pub mod list;
+pub mod validate;
+pub mod vars;
@@ -1,10 +1,13 @@
-use crate::template::{ProjectTemplate, TemplateFile};
+use crate::{
+    paths::{read_directory, DirectoryError},
+    render::{Definitions, Value},
+    template::{ProjectTemplate, TemplateFile, Variable},
+};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
 };
 use thiserror::Error;
-use walkdir::WalkDir;
 
 #[derive(Debug, Error)]
 pub enum FromDirectoryError {
@@ -14,14 +17,14 @@ pub enum FromDirectoryError {
     #[error("Output file could not be created")]
     OutputFileCreationError(PathBuf),
 
-    #[error("Unable to read content from file")]
-    FileReadError(PathBuf, String),
+    #[error("Unable to read directory")]
+    DirectoryReadError(#[source] DirectoryError),
 
-    #[error("Unable to convert path to string")]
-    PathConversionError(PathBuf),
+    #[error("Error opening definitions file")]
+    DefsFileError(PathBuf),
 
-    #[error("Unable to read path in directory")]
-    PathReadError(Option<PathBuf>),
+    #[error("Unable to read defs")]
+    DefsReadError(String),
 
     #[error("Error creating template file")]
     TemplateFileCreationError,
@@ -30,14 +33,34 @@ pub enum FromDirectoryError {
     SerializationError,
 }
 
-pub fn fd(src: &Path, output: &Path, force: bool) -> Result<(), FromDirectoryError> {
+/// Generate a template from the directory at `src`.
+///
+/// Paths ignored by `.gitignore` or `.pgenignore` files are left out, as are paths matching an
+/// `exclude` glob. If any `include` globs are given, only files matching one of them are kept.
+///
+/// Every occurrence of a value in `vars` or the definitions file at `defs_path` is replaced with a
+/// placeholder for its variable.
+pub fn fd(
+    src: &Path,
+    output: &Path,
+    force: bool,
+    exclude: &[String],
+    include: &[String],
+    vars: &[(String, String)],
+    defs_path: Option<&Path>,
+) -> Result<(), FromDirectoryError> {
     if output.exists() && !force {
         return Err(FromDirectoryError::OutputAlreadyExists(
             output.to_path_buf(),
         ));
     }
 
-    let templ = generate_template(src)?;
+    let mut values = vars.to_vec();
+    if let Some(p) = defs_path {
+        values.extend(read_values(p)?);
+    }
+
+    let templ = generate_template(src, exclude, include, &values)?;
 
     let Ok(out) = std::fs::File::create(output) else {
         return Err(FromDirectoryError::TemplateFileCreationError);
@@ -49,95 +72,134 @@ pub fn fd(src: &Path, output: &Path, force: bool) -> Result<(), FromDirectoryErr
     }
 }
 
-fn generate_template(root: &Path) -> Result<ProjectTemplate, FromDirectoryError> {
-    // Recursively collect all paths in src
-    let paths = get_paths_from_root(root)?;
-
-    // Read contents of paths that are files
-    let dirs = paths
-        .iter()
-        .filter(|p| p.is_dir())
-        .map(|p| {
-            let Ok(rel_path) = p.strip_prefix(root) else {
-                return Err(FromDirectoryError::PathConversionError(p.to_path_buf()));
-            };
-
-            let Some(str_path) = rel_path.to_str() else {
-                return Err(FromDirectoryError::PathConversionError(p.to_path_buf()));
-            };
+fn read_values(defs_path: &Path) -> Result<Vec<(String, String)>, FromDirectoryError> {
+    let Ok(defs_file) = std::fs::File::open(defs_path) else {
+        return Err(FromDirectoryError::DefsFileError(defs_path.to_path_buf()));
+    };
 
-            return Ok(str_path.to_string());
-        })
-        .collect::<Result<Vec<String>, FromDirectoryError>>()?;
+    let defs: Definitions = match serde_yaml::from_reader(defs_file) {
+        Ok(d) => d,
+        Err(e) => return Err(FromDirectoryError::DefsReadError(e.to_string())),
+    };
 
-    let files = paths
+    // Only single values can be found in the directory
+    return Ok(defs
         .into_iter()
-        .filter(|p| p.is_file())
-        .map(|p| {
-            let Ok(rel_path) = p.strip_prefix(root) else {
-                return Err(FromDirectoryError::PathConversionError(p.to_path_buf()));
-            };
-
-            let Some(str_path) = rel_path.to_str() else {
-                return Err(FromDirectoryError::PathConversionError(p.to_path_buf()));
-            };
+        .filter_map(|(name, value)| match value {
+            Value::Scalar(v) => Some((name, v)),
+            Value::List(_) => None,
+        })
+        .collect());
+}
 
-            let content = match std::fs::read_to_string(&p) {
-                Ok(c) => c.replace("\r\n", "\n"),
-                Err(e) => return Err(FromDirectoryError::FileReadError(p, e.to_string())),
-            };
+fn generate_template(
+    root: &Path,
+    exclude: &[String],
+    include: &[String],
+    values: &[(String, String)],
+) -> Result<ProjectTemplate, FromDirectoryError> {
+    let (dirs, files) = match read_directory(root, exclude, include) {
+        Ok(r) => r,
+        Err(e) => return Err(FromDirectoryError::DirectoryReadError(e)),
+    };
 
-            return Ok(TemplateFile {
-                path: str_path.to_string(),
-                content: content,
-            });
+    // Turn concrete values back into placeholders
+    let dirs: Vec<String> = dirs.iter().map(|d| reverse_sub(d, values)).collect();
+    let files: Vec<TemplateFile> = files
+        .into_iter()
+        .map(|f| TemplateFile {
+            path: reverse_sub(&f.path, values),
+            content: match f.encoding {
+                Some(_) => f.content,
+                None => reverse_sub(&f.content, values),
+            },
+            ..f
         })
-        .collect::<Result<Vec<TemplateFile>, FromDirectoryError>>()?;
+        .collect();
 
     // Iterate through dirs, file paths, and file contents to find vars
     let vars = get_template_vars(&dirs, &files);
 
     return Ok(ProjectTemplate {
-        variables: Vec::from_iter(vars),
+        metadata: None,
+        extends: None,
+        include: vec![],
+        hooks: Default::default(),
+        variables: vars.iter().map(|v| Variable::from(v.as_str())).collect(),
         directories: dirs,
-        files: files,
+        files,
     });
 }
 
-fn get_template_vars(dirs: &[String], files: &[TemplateFile]) -> HashSet<String> {
+/// Replace every occurrence of a value with a placeholder for its variable.
+///
+/// Longer values are matched first, so a value containing another is replaced whole.
+fn reverse_sub(str: &str, values: &[(String, String)]) -> String {
+    let mut values: Vec<&(String, String)> = values.iter().filter(|(_, v)| !v.is_empty()).collect();
+    if values.is_empty() {
+        return str.to_string();
+    }
+    values.sort_by_key(|(_, v)| std::cmp::Reverse(v.len()));
+
+    let placeholders: HashMap<&str, &str> = values
+        .iter()
+        .map(|(name, value)| (value.as_str(), name.as_str()))
+        .collect();
+    let pattern = values
+        .iter()
+        .map(|(_, v)| regex::escape(v))
+        .collect::<Vec<String>>()
+        .join("|");
+    let re = regex::Regex::new(&pattern).expect("Error compiling regex");
+
+    return re
+        .replace_all(str, |c: &regex::Captures| {
+            return format!("{{@ {} @}}", placeholders[&c[0]]);
+        })
+        .to_string();
+}
+
+pub(crate) fn get_template_vars(dirs: &[String], files: &[TemplateFile]) -> HashSet<String> {
     return files
         .iter()
-        .flat_map(|f| [&f.path, &f.content])
-        .chain(dirs)
-        .flat_map(|s| {
-            return get_vars_from_string(s);
+        .flat_map(|f| {
+            // Loop variables bound in a file's path are in scope for its content
+            let loop_vars = get_loop_vars(&f.path);
+            let content_vars = match f.encoding {
+                Some(_) => HashSet::new(),
+                None => get_vars_from_string(&f.content),
+            };
+            return get_vars_from_string(&f.path)
+                .into_iter()
+                .chain(content_vars)
+                .filter(move |v| !loop_vars.contains(v));
         })
+        .chain(dirs.iter().flat_map(|s| {
+            return get_vars_from_string(s);
+        }))
         .collect::<HashSet<String>>();
 }
 
-fn get_vars_from_string(str: &str) -> HashSet<String> {
-    let re = regex::Regex::new(r"\{@\s*(?P<var>\w+)\s*@\}").expect("Error compiling regex");
+pub(crate) fn get_vars_from_string(str: &str) -> HashSet<String> {
+    let re = regex::Regex::new(r"\{@\s*(?:if\s+|for\s+\w+\s+in\s+)?(?P<var>\w+)\s*(?:\|.*?)?@\}")
+        .expect("Error compiling regex");
+    let loop_vars = get_loop_vars(str);
 
     return re
         .captures_iter(str)
         .map(|c| c["var"].to_string())
+        .filter(|v| v != "else" && v != "end" && !loop_vars.contains(v))
         .collect::<HashSet<String>>();
 }
 
-fn get_paths_from_root(root: &Path) -> Result<Vec<PathBuf>, FromDirectoryError> {
-    return WalkDir::new(root)
-        .into_iter()
-        .map(|entry| {
-            return match entry {
-                Ok(e) => Ok(e.path().to_path_buf()),
-                Err(e) => {
-                    return Err(FromDirectoryError::PathReadError(
-                        e.path().and_then(|p| Some(p.to_path_buf())),
-                    ))
-                }
-            };
-        })
-        .collect();
+pub(crate) fn get_loop_vars(str: &str) -> HashSet<String> {
+    let re = regex::Regex::new(r"\{@\s*for\s+(?P<item>\w+)\s+in\s+\w+\s*@\}")
+        .expect("Error compiling regex");
+
+    return re
+        .captures_iter(str)
+        .map(|c| c["item"].to_string())
+        .collect::<HashSet<String>>();
 }
 
 #[cfg(test)]
@@ -146,7 +208,7 @@ mod tests {
 
     use crate::template::TemplateFile;
 
-    use super::{get_template_vars, get_vars_from_string};
+    use super::{get_template_vars, get_vars_from_string, reverse_sub};
 
     #[test]
     fn test_get_vars_from_string() {
@@ -155,6 +217,40 @@ mod tests {
         assert!(vars == HashSet::from(["namespace".to_string(), "project_name".to_string()]));
     }
 
+    #[test]
+    fn test_get_vars_from_string_blocks() {
+        let vars = get_vars_from_string("{@ if use_tests @}{@ project_name @}{@ else @}{@ end @}");
+
+        assert!(vars == HashSet::from(["use_tests".to_string(), "project_name".to_string()]));
+    }
+
+    #[test]
+    fn test_get_vars_from_string_loops() {
+        let vars = get_vars_from_string("{@ for m in modules @}add_subdirectory({@ m @}){@ end @}");
+
+        assert!(vars == HashSet::from(["modules".to_string()]));
+    }
+
+    #[test]
+    fn test_get_vars_from_string_filters() {
+        let vars =
+            get_vars_from_string(r#"{@ project_name | snake | upper @}{@ ns|replace("-", "_")@}"#);
+
+        assert!(vars == HashSet::from(["project_name".to_string(), "ns".to_string()]));
+    }
+
+    #[test]
+    fn test_reverse_sub() {
+        let values = vec![
+            ("project_name".to_string(), "fruit".to_string()),
+            ("namespace".to_string(), "passionfruit".to_string()),
+        ];
+
+        let actual = reverse_sub("namespace passionfruit { // fruit.hpp", &values);
+
+        assert!(actual == "namespace {@ namespace @} { // {@ project_name @}.hpp");
+    }
+
     #[test]
     fn test_get_template_vars() {
         let dirs = vec![
@@ -165,6 +261,8 @@ mod tests {
         let files = vec![TemplateFile {
             path: "test/{@project_name@}.cpp".to_string(),
             content: "{@namespace@}{project_name@}".to_string(),
+            encoding: None,
+            mode: None,
         }];
 
         let actual = get_template_vars(&dirs, &files);
@@ -1,15 +1,25 @@
 use crate::{
-    render::render_template,
-    template::{read_template, ProjectTemplate, RenderedTemplate},
+    cmd::fd::{get_template_vars, get_vars_from_string},
+    paths::escapes_root,
+    prompt::{confirm, prompt_for_missing},
+    render::{apply_defaults, render_string, render_template, Definitions, RenderError},
+    template::{load_template_source, Hooks, ProjectTemplate, RenderedTemplate, TemplateError},
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::File,
     io::Write,
     path::{Path, PathBuf},
 };
 use thiserror::Error;
 
+mod answers;
+mod plan;
+
+use answers::{Answers, Provenance, ANSWERS_NAME};
+use plan::Plan;
+pub use plan::PlanFormat;
+
 #[derive(Debug, Error)]
 #[error("Error generating project")]
 pub enum GenerationError {
@@ -20,7 +30,7 @@ pub enum GenerationError {
     TemplateFileError(PathBuf),
 
     #[error("Unable to read template")]
-    TemplateReadError(String),
+    TemplateReadError(#[source] TemplateError),
 
     #[error("Error opening definitions file")]
     DefsFileError(PathBuf),
@@ -28,90 +38,1062 @@ pub enum GenerationError {
     #[error("Unable to read defs")]
     DefsReadError(String),
 
+    #[error("Unable to read variable definition from terminal")]
+    PromptError(String),
+
+    #[error("Variables used by the template are not defined")]
+    UndefinedVariablesError(Vec<String>),
+
+    #[error("Variable definition breaks a rule declared by the template")]
+    InvalidDefinitionError(String, String),
+
     #[error("Error rendering template")]
-    TemplateRenderError,
+    TemplateRenderError(#[source] RenderError),
+
+    #[error("Rendered path is outside of the project root")]
+    PathEscapesRootError(PathBuf),
+
+    #[error("File already exists in the project root")]
+    FileExistsError(PathBuf),
+
+    #[error("A directory is in the way of a generated file")]
+    DirectoryInTheWayError(PathBuf),
 
     #[error("Error writing template to desitination")]
     TemplateWriteError(PathBuf),
 
     #[error("Error creating parent directory")]
     ParentDirectoryCreateError(PathBuf),
+
+    #[error("Error setting file permissions")]
+    PermissionsError(PathBuf),
+
+    #[error("Error creating staging directory")]
+    StagingError(String),
+
+    #[error("Error moving generated files into the project root")]
+    CommitError(PathBuf),
+
+    #[error("Unable to undo a failed generation, files may be left behind")]
+    RollbackError(Vec<PathBuf>, Option<PathBuf>),
+
+    #[error("Error printing dry run")]
+    PlanOutputError(String),
+
+    #[error("Hook command failed")]
+    HookError(String, String),
+
+    #[error("Error writing answers file")]
+    AnswersWriteError(String),
 }
 
-pub fn gen(root: &Path, templ_path: &Path, defs_path: &Path) -> Result<(), GenerationError> {
+/// What to do when a generated file already exists in the project root.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Keep the existing file
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+    /// Stop before writing anything
+    #[default]
+    Fail,
+    /// Ask for each file
+    Prompt,
+}
+
+#[derive(Debug, Default)]
+pub struct GenOptions {
+    /// Generate into a root that already exists
+    pub into_existing: bool,
+
+    /// What to do with files that already exist when generating into an existing root
+    pub on_conflict: ConflictPolicy,
+
+    /// Print what would be generated instead of writing it
+    pub dry_run: bool,
+
+    /// How a dry run is printed
+    pub format: PlanFormat,
+
+    /// Skip the template's pre_gen and post_gen commands
+    pub no_hooks: bool,
+}
+
+/// Files written by a generation, and what happened to files that already existed.
+#[derive(Debug, Default)]
+struct WriteReport {
+    created: Vec<PathBuf>,
+    skipped: Vec<PathBuf>,
+    overwritten: Vec<PathBuf>,
+}
+
+pub fn gen(
+    root: &Path,
+    templ_path: &Path,
+    defs_path: Option<&Path>,
+    options: &GenOptions,
+) -> Result<(), GenerationError> {
     // Check if root exists
-    if root.exists() {
+    if root.exists() && !options.into_existing {
         return Err(GenerationError::RootExistsError(root.to_path_buf()));
     }
 
     // Read template at templ_path
-    let Ok(templ_file) = File::open(templ_path) else {
-        return Err(GenerationError::TemplateFileError(templ_path.to_path_buf()));
+    let (templ, checkout) = match load_template_source(templ_path) {
+        Ok(t) => t,
+        Err(TemplateError::FileError(p)) => return Err(GenerationError::TemplateFileError(p)),
+        Err(e) => return Err(GenerationError::TemplateReadError(e)),
     };
 
-    let templ = match read_template(templ_file) {
-        Ok(t) => t,
-        Err(e) => return Err(GenerationError::TemplateReadError(e.to_string())),
+    // Note where the template came from before anything is added to it
+    let source = match std::fs::canonicalize(templ_path) {
+        Ok(p) => p.display().to_string(),
+        Err(_) => templ_path.display().to_string(),
+    };
+    let provenance = match Provenance::new(source, checkout.map(|c| c.commit), &templ) {
+        Ok(p) => p,
+        Err(e) => return Err(GenerationError::AnswersWriteError(e.to_string())),
     };
 
-    // Read defs at defs path
-    let Ok(defs_file) = File::open(defs_path) else {
-        return Err(GenerationError::DefsFileError(defs_path.to_path_buf()));
+    // Read defs at defs path, if one was given
+    let mut defs = match defs_path {
+        Some(p) => read_defs(p)?,
+        None => HashMap::new(),
     };
 
-    let defs = match serde_yaml::from_reader(defs_file) {
-        Ok(d) => d,
-        Err(e) => return Err(GenerationError::DefsReadError(e.to_string())),
+    // Values left out of a defs file take their defaults, as do the values a dry run would prompt
+    // for, so its plan shows what an empty answer would generate
+    if defs_path.is_some() || options.dry_run {
+        if let Err(e) = apply_defaults(&templ.variables, &mut defs) {
+            return Err(GenerationError::TemplateRenderError(e));
+        }
+    }
+
+    // Prompt for any variables the defs file didn't supply, a dry run reports them instead
+    let stdin = std::io::stdin();
+    if !options.dry_run {
+        if let Err(e) = prompt_for_missing(
+            &templ.variables,
+            &mut defs,
+            &mut stdin.lock(),
+            &mut std::io::stdout(),
+        ) {
+            return Err(GenerationError::PromptError(e.to_string()));
+        }
+    }
+
+    let answers = Answers {
+        provenance: Some(provenance),
+        defs: defs.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
     };
 
     // Generate project
-    return generate_from_template(root, templ, &defs);
+    return generate_from_template(root, templ, &defs, &answers, options);
+}
+
+fn read_defs(defs_path: &Path) -> Result<Definitions, GenerationError> {
+    let Ok(defs_file) = File::open(defs_path) else {
+        return Err(GenerationError::DefsFileError(defs_path.to_path_buf()));
+    };
+
+    // A project's answers file reads as its definitions
+    return match serde_yaml::from_reader::<File, Answers>(defs_file) {
+        Ok(a) => Ok(a.into_definitions()),
+        Err(e) => Err(GenerationError::DefsReadError(e.to_string())),
+    };
 }
 
 fn generate_from_template(
     root: &Path,
     templ: ProjectTemplate,
-    defs: &HashMap<String, String>,
+    defs: &Definitions,
+    answers: &Answers,
+    options: &GenOptions,
 ) -> Result<(), GenerationError> {
+    if options.dry_run {
+        return dry_run(root, templ, defs, answers, options.format);
+    }
+
+    // Check defs against the template's variables
+    for unused in check_definitions(&templ, defs)? {
+        eprintln!(
+            "Warning: definition '{}' is not used by the template",
+            unused
+        );
+    }
+
+    // Render hook commands with the same definitions as the template
+    let hooks = match options.no_hooks {
+        true => Hooks::default(),
+        false => render_hooks(&templ.hooks, defs)?,
+    };
+
     // Render template
-    let rendered = render_template(templ, defs);
+    let rendered = render_with_answers(templ, defs, answers)?;
+
+    // Make sure nothing will be written outside of root
+    for path in rendered.directories.iter().chain(rendered.files.keys()) {
+        check_path_in_root(root, path)?;
+    }
+
+    run_hooks(Path::new("."), root, &hooks.pre_gen)?;
 
     // Write to desitination
-    if let Err(e) = write_rendered_template(root, &rendered) {
-        eprintln!("Error writing rendered template");
-        std::fs::remove_dir_all(root).expect("Error removing root directory");
-        return Err(e);
+    let report =
+        match write_rendered_template(root, &rendered, options.on_conflict, &hooks.post_gen) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Error writing rendered template");
+                return Err(e);
+            }
+        };
+
+    if options.into_existing {
+        for (action, paths) in [
+            ("created", &report.created),
+            ("skipped", &report.skipped),
+            ("overwritten", &report.overwritten),
+        ] {
+            for p in paths {
+                println!("{} {}", action, p.display());
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+/// Render the template and add the answers file to it.
+fn render_with_answers(
+    templ: ProjectTemplate,
+    defs: &Definitions,
+    answers: &Answers,
+) -> Result<RenderedTemplate, GenerationError> {
+    let mut rendered = match render_template(templ, defs) {
+        Ok(r) => r,
+        Err(e) => return Err(GenerationError::TemplateRenderError(e)),
+    };
+
+    let file = match answers.to_file() {
+        Ok(f) => f,
+        Err(e) => return Err(GenerationError::AnswersWriteError(e.to_string())),
+    };
+    rendered.files.insert(PathBuf::from(ANSWERS_NAME), file);
+
+    return Ok(rendered);
+}
+
+/// Render the template and print what would be written, without touching disk.
+fn dry_run(
+    root: &Path,
+    templ: ProjectTemplate,
+    defs: &Definitions,
+    answers: &Answers,
+    format: PlanFormat,
+) -> Result<(), GenerationError> {
+    let unresolved = undefined_vars(&template_vars(&templ), defs);
+
+    let rendered = render_with_answers(templ, defs, answers)?;
+
+    for path in rendered.directories.iter().chain(rendered.files.keys()) {
+        check_path_in_root(root, path)?;
+    }
+
+    let plan = Plan::new(root, &rendered, unresolved);
+    match format {
+        PlanFormat::Text => print!("{}", plan.to_text()),
+        PlanFormat::Json => match serde_json::to_string_pretty(&plan) {
+            Ok(json) => println!("{}", json),
+            Err(e) => return Err(GenerationError::PlanOutputError(e.to_string())),
+        },
     }
 
     return Ok(());
 }
-fn write_rendered_template(root: &Path, templ: &RenderedTemplate) -> Result<(), GenerationError> {
+
+/// Every variable the template declares or uses.
+fn template_vars(templ: &ProjectTemplate) -> HashSet<String> {
+    let mut vars: HashSet<String> = get_template_vars(&templ.directories, &templ.files);
+    vars.extend(templ.variables.iter().map(|v| v.name.clone()));
+    vars.extend(templ.hooks.commands().flat_map(|c| get_vars_from_string(c)));
+
+    return vars;
+}
+
+/// Variables in `vars` that have no definition, sorted by name.
+fn undefined_vars(vars: &HashSet<String>, defs: &Definitions) -> Vec<String> {
+    let mut missing: Vec<String> = vars
+        .iter()
+        .filter(|v| !defs.contains_key(*v))
+        .cloned()
+        .collect();
+    missing.sort();
+
+    return missing;
+}
+
+/// Check that every variable the template declares or uses is defined, and that definitions follow
+/// the rules their variable declares.
+///
+/// Returns the definitions that the template doesn't use.
+fn check_definitions(
+    templ: &ProjectTemplate,
+    defs: &Definitions,
+) -> Result<Vec<String>, GenerationError> {
+    let vars = template_vars(templ);
+    let missing = undefined_vars(&vars, defs);
+    if !missing.is_empty() {
+        return Err(GenerationError::UndefinedVariablesError(missing));
+    }
+
+    for var in &templ.variables {
+        if let Err(rule) = var.validate(&defs[&var.name]) {
+            return Err(GenerationError::InvalidDefinitionError(
+                var.name.clone(),
+                rule,
+            ));
+        }
+    }
+
+    let mut unused: Vec<String> = defs
+        .keys()
+        .filter(|k| !vars.contains(*k))
+        .cloned()
+        .collect();
+    unused.sort();
+
+    return Ok(unused);
+}
+
+/// Check that `path` stays inside `root` once joined to it, including through existing symlinks.
+fn check_path_in_root(root: &Path, path: &Path) -> Result<(), GenerationError> {
+    let escapes = Err(GenerationError::PathEscapesRootError(path.to_path_buf()));
+
+    if escapes_root(path) {
+        return escapes;
+    }
+
+    // Nothing inside root can be a symlink if it doesn't exist yet
+    let Ok(canon_root) = root.canonicalize() else {
+        return Ok(());
+    };
+
+    // Resolve the deepest part of the path that already exists
+    let full = root.join(path);
+    let Some(existing) = full.ancestors().find(|p| p.symlink_metadata().is_ok()) else {
+        return Ok(());
+    };
+
+    return match existing.canonicalize() {
+        Ok(p) if p.starts_with(&canon_root) => Ok(()),
+        _ => escapes,
+    };
+}
+
+/// Write the rendered template to `root`.
+///
+/// Everything is first written to a staging directory next to `root`, then moved into place, so a
+/// failure never leaves a half written project behind or touches files that were already there.
+fn write_rendered_template(
+    root: &Path,
+    templ: &RenderedTemplate,
+    on_conflict: ConflictPolicy,
+    post_gen: &[String],
+) -> Result<WriteReport, GenerationError> {
+    let report = resolve_conflicts(root, templ, on_conflict)?;
+
+    let parent = staging_parent(root)?;
+
+    // Ancestors that don't exist yet are removed again if anything fails
+    let missing: Vec<PathBuf> = parent
+        .ancestors()
+        .take_while(|a| !a.exists())
+        .map(Path::to_path_buf)
+        .collect();
+
+    let result = match std::fs::create_dir_all(&parent) {
+        Ok(_) => stage_and_commit(root, &parent, templ, &report, post_gen),
+        Err(_) => Err(GenerationError::ParentDirectoryCreateError(parent.clone())),
+    };
+
+    if result.is_err() {
+        for dir in &missing {
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+
+    return result.map(|_| report);
+}
+
+/// Directory the staging directory goes in, the parent of the absolute root so it is never inside
+/// the root itself.
+fn staging_parent(root: &Path) -> Result<PathBuf, GenerationError> {
+    let absolute = match root.exists() {
+        true => std::fs::canonicalize(root),
+        false => std::path::absolute(root),
+    };
+
+    return match absolute.as_deref().map(Path::parent) {
+        Ok(Some(p)) => Ok(p.to_path_buf()),
+        _ => Err(GenerationError::ParentDirectoryCreateError(
+            root.to_path_buf(),
+        )),
+    };
+}
+
+/// Write the files in a staging directory inside `parent` and move them into root.
+fn stage_and_commit(
+    root: &Path,
+    parent: &Path,
+    templ: &RenderedTemplate,
+    report: &WriteReport,
+    post_gen: &[String],
+) -> Result<(), GenerationError> {
+    // Removed when dropped, including on every error below
+    let staging = match tempfile::Builder::new().prefix(".pgen-").tempdir_in(parent) {
+        Ok(s) => s,
+        Err(e) => return Err(GenerationError::StagingError(e.to_string())),
+    };
+    let stage = staging.path().join("root");
+
+    let files: Vec<&PathBuf> = report.created.iter().chain(&report.overwritten).collect();
+    write_files(&stage, templ, &files)?;
+
+    if !root.exists() {
+        if std::fs::rename(&stage, root).is_err() {
+            return Err(GenerationError::CommitError(root.to_path_buf()));
+        }
+
+        // Move the new root back into staging so it is removed along with it
+        if let Err(e) = run_hooks(root, root, post_gen) {
+            if std::fs::rename(root, &stage).is_err() {
+                report_rollback_error(&GenerationError::RollbackError(
+                    vec![root.to_path_buf()],
+                    None,
+                ));
+            }
+            return Err(e);
+        }
+    } else {
+        // Everything is put back if a move or a hook fails
+        let backup = staging.path().join("backup");
+        let mut changes = Vec::new();
+        let result = match move_into_existing(&stage, root, templ, &files, &backup, &mut changes) {
+            Ok(()) => run_hooks(root, root, post_gen),
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            let failed = rollback(changes);
+            if !failed.is_empty() {
+                // Keep the originals that couldn't be restored
+                let kept = staging.keep();
+                report_rollback_error(&GenerationError::RollbackError(
+                    failed,
+                    Some(kept.join("backup")),
+                ));
+            }
+            return Err(e);
+        }
+    }
+
+    return Ok(());
+}
+
+/// Decide which files will be written, following `on_conflict` for files that already exist.
+fn resolve_conflicts(
+    root: &Path,
+    templ: &RenderedTemplate,
+    on_conflict: ConflictPolicy,
+) -> Result<WriteReport, GenerationError> {
+    let mut report = WriteReport::default();
+    let mut paths: Vec<&PathBuf> = templ.files.keys().collect();
+    paths.sort();
+
+    for p in paths {
+        // No policy replaces a directory with a file
+        if root.join(p).is_dir() {
+            return Err(GenerationError::DirectoryInTheWayError(p.to_path_buf()));
+        }
+
+        if !root.join(p).exists() {
+            report.created.push(p.to_path_buf());
+            continue;
+        }
+
+        // The answers file always records the latest generation, so templates can be added to a
+        // project one after another
+        if p == Path::new(ANSWERS_NAME) {
+            report.overwritten.push(p.to_path_buf());
+            continue;
+        }
+
+        let overwrite = match on_conflict {
+            ConflictPolicy::Skip => false,
+            ConflictPolicy::Overwrite => true,
+            ConflictPolicy::Fail => return Err(GenerationError::FileExistsError(p.to_path_buf())),
+            ConflictPolicy::Prompt => {
+                let stdin = std::io::stdin();
+                match confirm(
+                    &format!("{} already exists, overwrite it?", p.display()),
+                    &mut stdin.lock(),
+                    &mut std::io::stdout(),
+                ) {
+                    Ok(o) => o,
+                    Err(e) => return Err(GenerationError::PromptError(e.to_string())),
+                }
+            }
+        };
+
+        match overwrite {
+            true => report.overwritten.push(p.to_path_buf()),
+            false => report.skipped.push(p.to_path_buf()),
+        }
+    }
+
+    return Ok(report);
+}
+
+fn write_files(
+    dest: &Path,
+    templ: &RenderedTemplate,
+    files: &[&PathBuf],
+) -> Result<(), GenerationError> {
+    if std::fs::create_dir_all(dest).is_err() {
+        return Err(GenerationError::TemplateWriteError(dest.to_path_buf()));
+    }
+
     for dir in &templ.directories {
-        if let Err(_) = std::fs::create_dir_all(root.join(dir)) {
+        if std::fs::create_dir_all(dest.join(dir)).is_err() {
             return Err(GenerationError::TemplateWriteError(dir.to_path_buf()));
         }
     }
 
-    for (p, c) in &templ.files {
-        let path = root.join(p);
+    for p in files {
+        let c = &templ.files[*p];
+        let path = dest.join(p);
         if let Some(par) = path.parent() {
-            if !par.exists() {
-                if let Err(_) = std::fs::create_dir_all(par) {
-                    return Err(GenerationError::ParentDirectoryCreateError(
-                        par.to_path_buf(),
-                    ));
-                }
+            if !par.exists() && std::fs::create_dir_all(par).is_err() {
+                return Err(GenerationError::ParentDirectoryCreateError(
+                    par.to_path_buf(),
+                ));
             }
         }
 
-        let Ok(mut f) = std::fs::File::create(root.join(p)) else {
+        let Ok(mut f) = std::fs::File::create(&path) else {
             return Err(GenerationError::TemplateWriteError(p.to_path_buf()));
         };
 
-        if let Err(_) = write!(f, "{}", c) {
+        if f.write_all(&c.content).is_err() {
             return Err(GenerationError::TemplateWriteError(p.to_path_buf()));
         }
+
+        if let Some(mode) = c.mode {
+            set_mode(&path, mode.0)?;
+        }
     }
 
     return Ok(());
 }
+
+/// Something done to an existing root that can be undone.
+enum Change {
+    CreatedDir(PathBuf),
+    CreatedFile(PathBuf),
+    /// File in root that was moved to the backup path before being overwritten
+    Replaced(PathBuf, PathBuf),
+}
+
+/// Move staged files into an existing root, recording each change so it can be undone while the
+/// backup exists.
+fn move_into_existing(
+    stage: &Path,
+    root: &Path,
+    templ: &RenderedTemplate,
+    files: &[&PathBuf],
+    backup: &Path,
+    changes: &mut Vec<Change>,
+) -> Result<(), GenerationError> {
+    let parents = files.iter().filter_map(|p| p.parent());
+    for dir in templ.directories.iter().map(|d| d.as_path()).chain(parents) {
+        // Create each missing ancestor on its own so it can be removed again
+        let mut missing: Vec<PathBuf> = root
+            .join(dir)
+            .ancestors()
+            .take_while(|a| !a.exists())
+            .map(|a| a.to_path_buf())
+            .collect();
+        missing.reverse();
+
+        for d in missing {
+            if std::fs::create_dir(&d).is_err() {
+                return Err(GenerationError::CommitError(d));
+            }
+            changes.push(Change::CreatedDir(d));
+        }
+    }
+
+    for p in files {
+        let target = root.join(p);
+
+        if target.exists() {
+            let saved = backup.join(p);
+            if let Some(par) = saved.parent() {
+                if std::fs::create_dir_all(par).is_err() {
+                    return Err(GenerationError::CommitError(target));
+                }
+            }
+            if std::fs::rename(&target, &saved).is_err() {
+                return Err(GenerationError::CommitError(target));
+            }
+            changes.push(Change::Replaced(target.clone(), saved));
+        } else {
+            changes.push(Change::CreatedFile(target.clone()));
+        }
+
+        if std::fs::rename(stage.join(p), &target).is_err() {
+            return Err(GenerationError::CommitError(target));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Undo changes in reverse order, only removing what pgen created.
+///
+/// Keeps going past changes that can't be undone and returns their paths.
+fn rollback(changes: Vec<Change>) -> Vec<PathBuf> {
+    let mut failed = Vec::new();
+    for change in changes.into_iter().rev() {
+        let undone = match &change {
+            Change::CreatedFile(p) => !p.exists() || std::fs::remove_file(p).is_ok(),
+            // A hook may have written into a directory pgen created
+            Change::CreatedDir(p) => !p.exists() || std::fs::remove_dir_all(p).is_ok(),
+            Change::Replaced(p, saved) => {
+                (!p.exists() || std::fs::remove_file(p).is_ok())
+                    && std::fs::rename(saved, p).is_ok()
+            }
+        };
+
+        if !undone {
+            let (Change::CreatedFile(p) | Change::CreatedDir(p) | Change::Replaced(p, _)) = change;
+            failed.push(p);
+        }
+    }
+
+    return failed;
+}
+
+/// Report a failed rollback while the error that caused it is returned.
+fn report_rollback_error(error: &GenerationError) {
+    eprintln!("{}: {:?}", error, error);
+}
+
+fn render_hooks(hooks: &Hooks, defs: &Definitions) -> Result<Hooks, GenerationError> {
+    let render = |commands: &[String]| {
+        return commands
+            .iter()
+            .map(|c| match render_string(c.clone(), defs) {
+                Ok(r) => Ok(r),
+                Err(e) => Err(GenerationError::TemplateRenderError(e)),
+            })
+            .collect::<Result<Vec<String>, GenerationError>>();
+    };
+
+    return Ok(Hooks {
+        pre_gen: render(&hooks.pre_gen)?,
+        post_gen: render(&hooks.post_gen)?,
+    });
+}
+
+/// Run each command through the shell in `dir`, stopping at the first one that fails.
+///
+/// The project root is passed to the commands in the `PGEN_ROOT` environment variable.
+fn run_hooks(dir: &Path, root: &Path, commands: &[String]) -> Result<(), GenerationError> {
+    let root = std::path::absolute(root).unwrap_or(root.to_path_buf());
+
+    for command in commands {
+        println!("Running {}", command);
+
+        let mut shell = match cfg!(windows) {
+            true => std::process::Command::new("cmd"),
+            false => std::process::Command::new("sh"),
+        };
+        shell.arg(if cfg!(windows) { "/C" } else { "-c" });
+
+        let status = shell
+            .arg(command)
+            .current_dir(dir)
+            .env("PGEN_ROOT", &root)
+            .status();
+
+        match status {
+            Ok(s) if s.success() => {}
+            Ok(s) => return Err(GenerationError::HookError(command.clone(), s.to_string())),
+            Err(e) => return Err(GenerationError::HookError(command.clone(), e.to_string())),
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<(), GenerationError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    return match std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(GenerationError::PermissionsError(path.to_path_buf())),
+    };
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<(), GenerationError> {
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_definitions, check_path_in_root, generate_from_template, render_hooks, rollback,
+        staging_parent, template_vars, write_rendered_template, Answers, Change, ConflictPolicy,
+        GenOptions, GenerationError, ANSWERS_NAME,
+    };
+    use crate::{
+        prompt::prompt_for_missing,
+        template::{
+            load_template, Hooks, ProjectTemplate, RenderedFile, RenderedTemplate, TemplateFile,
+            Variable,
+        },
+    };
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    fn template() -> ProjectTemplate {
+        return ProjectTemplate {
+            variables: vec![Variable::from("project_name")],
+            directories: vec!["include/{@ namespace @}".to_string()],
+            files: vec![TemplateFile {
+                path: "src/main.cpp".to_string(),
+                content: "{@ project_name @}".to_string(),
+                encoding: None,
+                mode: None,
+            }],
+            ..ProjectTemplate::default()
+        };
+    }
+
+    #[test]
+    fn missing_definitions() {
+        let defs = HashMap::new();
+
+        let Err(GenerationError::UndefinedVariablesError(missing)) =
+            check_definitions(&template(), &defs)
+        else {
+            panic!("Expected undefined variables error");
+        };
+
+        assert!(missing == vec!["namespace".to_string(), "project_name".to_string()]);
+    }
+
+    #[test]
+    fn unused_definitions() {
+        let defs = HashMap::from([
+            ("namespace".to_string(), "passion".into()),
+            ("project_name".to_string(), "fruit".into()),
+            ("license".to_string(), "MIT".into()),
+        ]);
+
+        let unused = check_definitions(&template(), &defs).expect("Error checking definitions");
+
+        assert!(unused == vec!["license".to_string()]);
+    }
+
+    #[test]
+    fn invalid_definition() {
+        let mut templ = template();
+        templ.variables.push(Variable {
+            pattern: Some(r"\w+".to_string()),
+            ..Variable::from("namespace")
+        });
+        let defs = HashMap::from([
+            ("namespace".to_string(), "passion fruit".into()),
+            ("project_name".to_string(), "fruit".into()),
+        ]);
+
+        let Err(GenerationError::InvalidDefinitionError(var, _)) = check_definitions(&templ, &defs)
+        else {
+            panic!("Expected invalid definition error");
+        };
+
+        assert!(var == "namespace");
+    }
+
+    #[test]
+    fn paths_outside_root() {
+        let root = Path::new("does/not/exist");
+
+        assert!(check_path_in_root(root, Path::new("include/../src/main.cpp")).is_ok());
+        assert!(check_path_in_root(root, Path::new("")).is_ok());
+        assert!(check_path_in_root(root, Path::new("../../.bashrc")).is_err());
+        assert!(check_path_in_root(root, Path::new("src/../../x")).is_err());
+        assert!(check_path_in_root(root, Path::new("/etc/passwd")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_outside_root() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path().join("root");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+        assert!(check_path_in_root(&root, Path::new("src/main.cpp")).is_ok());
+        assert!(check_path_in_root(&root, Path::new("new/main.cpp")).is_ok());
+        assert!(check_path_in_root(&root, Path::new("link/main.cpp")).is_err());
+    }
+
+    fn rendered() -> RenderedTemplate {
+        return RenderedTemplate {
+            directories: vec![PathBuf::from("src")],
+            files: HashMap::from([
+                (
+                    PathBuf::from("src/main.cpp"),
+                    RenderedFile {
+                        content: b"new".to_vec(),
+                        mode: None,
+                    },
+                ),
+                (
+                    PathBuf::from("README.md"),
+                    RenderedFile {
+                        content: b"new".to_vec(),
+                        mode: None,
+                    },
+                ),
+            ]),
+        };
+    }
+
+    #[test]
+    fn conflict_policies() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path();
+        std::fs::write(root.join("README.md"), "old").unwrap();
+
+        let Err(GenerationError::FileExistsError(p)) =
+            write_rendered_template(root, &rendered(), ConflictPolicy::Fail, &[])
+        else {
+            panic!("Expected file exists error");
+        };
+        assert!(p == Path::new("README.md"));
+        assert!(!root.join("src").exists());
+
+        let report = write_rendered_template(root, &rendered(), ConflictPolicy::Skip, &[]).unwrap();
+        assert!(report.created == vec![PathBuf::from("src/main.cpp")]);
+        assert!(report.skipped == vec![PathBuf::from("README.md")]);
+        assert!(std::fs::read_to_string(root.join("README.md")).unwrap() == "old");
+
+        let report =
+            write_rendered_template(root, &rendered(), ConflictPolicy::Overwrite, &[]).unwrap();
+        assert!(report.overwritten.len() == 2);
+        assert!(std::fs::read_to_string(root.join("README.md")).unwrap() == "new");
+    }
+
+    #[test]
+    fn directory_in_the_way() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path();
+        std::fs::create_dir(root.join("README.md")).unwrap();
+
+        for policy in [ConflictPolicy::Skip, ConflictPolicy::Overwrite] {
+            let Err(GenerationError::DirectoryInTheWayError(p)) =
+                write_rendered_template(root, &rendered(), policy, &[])
+            else {
+                panic!("Expected directory in the way error");
+            };
+            assert!(p == Path::new("README.md"));
+            assert!(root.join("README.md").is_dir());
+            assert!(!root.join("src").exists());
+        }
+    }
+
+    #[test]
+    fn rollback_keeps_going() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path();
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.cpp"), "new").unwrap();
+        std::fs::write(root.join("README.md"), "new").unwrap();
+
+        // The backup of README.md is missing so it can't be restored
+        let failed = rollback(vec![
+            Change::CreatedDir(root.join("src")),
+            Change::Replaced(root.join("README.md"), root.join("backup/README.md")),
+            Change::CreatedFile(root.join("src/main.cpp")),
+        ]);
+
+        assert!(failed == vec![root.join("README.md")]);
+        assert!(!root.join("src").exists());
+    }
+
+    #[test]
+    fn answers_file_rewritten() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path();
+        std::fs::write(root.join(ANSWERS_NAME), "old").unwrap();
+
+        let mut templ = rendered();
+        templ.files.insert(
+            PathBuf::from(ANSWERS_NAME),
+            RenderedFile {
+                content: b"new".to_vec(),
+                mode: None,
+            },
+        );
+
+        for policy in [ConflictPolicy::Fail, ConflictPolicy::Skip] {
+            std::fs::write(root.join(ANSWERS_NAME), "old").unwrap();
+            let report = write_rendered_template(root, &templ, policy, &[]).unwrap();
+            assert!(report.overwritten == vec![PathBuf::from(ANSWERS_NAME)]);
+            assert!(std::fs::read_to_string(root.join(ANSWERS_NAME)).unwrap() == "new");
+        }
+    }
+
+    #[test]
+    fn failed_write_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path().join("project");
+
+        // A file can't also be a directory
+        let mut templ = rendered();
+        templ.files.insert(
+            PathBuf::from("README.md/nested"),
+            RenderedFile {
+                content: b"new".to_vec(),
+                mode: None,
+            },
+        );
+
+        // Missing ancestors are created for the new root and removed again
+        let nested = dir.path().join("nested/deeper/project");
+        assert!(write_rendered_template(&nested, &templ, ConflictPolicy::Fail, &[]).is_err());
+        assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("README.md"), "old").unwrap();
+
+        assert!(write_rendered_template(&root, &templ, ConflictPolicy::Overwrite, &[]).is_err());
+        assert!(std::fs::read_to_string(root.join("README.md")).unwrap() == "old");
+        assert!(!root.join("src").exists());
+        assert!(std::fs::read_dir(dir.path()).unwrap().count() == 1);
+    }
+
+    #[test]
+    fn staging_outside_root() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let parent = std::fs::canonicalize(dir.path()).unwrap();
+
+        assert!(staging_parent(&root.join("src/..")).unwrap() == parent);
+        assert!(staging_parent(&root.join("new")).unwrap() == root);
+        assert!(
+            staging_parent(Path::new(".")).unwrap()
+                == std::env::current_dir().unwrap().parent().unwrap()
+        );
+    }
+
+    #[test]
+    fn generate_into_new_root() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path().join("nested/project");
+
+        let report =
+            write_rendered_template(&root, &rendered(), ConflictPolicy::Fail, &[]).unwrap();
+
+        assert!(report.created.len() == 2);
+        assert!(std::fs::read_to_string(root.join("src/main.cpp")).unwrap() == "new");
+        assert!(
+            std::fs::read_dir(dir.path().join("nested"))
+                .unwrap()
+                .count()
+                == 1
+        );
+    }
+
+    #[test]
+    fn generate_from_dir_without_manifest() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path().join("project");
+
+        let templ = load_template(Path::new("tests/templ-dir")).expect("Error loading template");
+        let mut defs = HashMap::new();
+        let mut input = "passion\nfruit\n".as_bytes();
+        prompt_for_missing(&templ.variables, &mut defs, &mut input, &mut Vec::new())
+            .expect("Error prompting");
+
+        let answers = Answers {
+            provenance: None,
+            defs: defs.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        };
+        generate_from_template(&root, templ, &defs, &answers, &GenOptions::default())
+            .expect("Error generating project");
+
+        let header =
+            std::fs::read_to_string(root.join("include/fruit/{project_name}.hpp")).unwrap();
+        assert!(header.contains("namespace passion"));
+        assert!(root.join("src/main.cpp").exists());
+    }
+
+    #[test]
+    fn hook_variables() {
+        let mut templ = template();
+        templ.hooks = Hooks {
+            pre_gen: vec![],
+            post_gen: vec!["git init {@ vcs_dir @}".to_string()],
+        };
+        assert!(template_vars(&templ).contains("vcs_dir"));
+
+        let defs = HashMap::from([("vcs_dir".to_string(), ".".into())]);
+        let hooks = render_hooks(&templ.hooks, &defs).unwrap();
+        assert!(hooks.post_gen == vec!["git init ."]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn post_gen_hooks() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path().join("project");
+
+        let hooks = ["touch hooked".to_string()];
+        write_rendered_template(&root, &rendered(), ConflictPolicy::Fail, &hooks).unwrap();
+        assert!(root.join("hooked").exists());
+
+        // A failing hook removes a new root
+        let failing = ["touch ran".to_string(), "exit 3".to_string()];
+        let other = dir.path().join("other");
+        assert!(matches!(
+            write_rendered_template(&other, &rendered(), ConflictPolicy::Fail, &failing),
+            Err(GenerationError::HookError(..))
+        ));
+        assert!(!other.exists());
+
+        // and puts an existing root back the way it was, including directories a hook wrote into
+        std::fs::write(root.join("README.md"), "old").unwrap();
+        std::fs::remove_dir_all(root.join("src")).unwrap();
+        let failing = ["touch src/cache".to_string(), "exit 1".to_string()];
+        assert!(matches!(
+            write_rendered_template(&root, &rendered(), ConflictPolicy::Overwrite, &failing),
+            Err(GenerationError::HookError(..))
+        ));
+        assert!(std::fs::read_to_string(root.join("README.md")).unwrap() == "old");
+        assert!(!root.join("src").exists());
+        assert!(root.join("hooked").exists());
+        assert!(std::fs::read_dir(dir.path()).unwrap().count() == 1);
+    }
+}
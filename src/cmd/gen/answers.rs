@@ -0,0 +1,133 @@
+use crate::{
+    render::{Definitions, Value},
+    template::{ProjectTemplate, RenderedFile},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Name of the file recording how a project was generated.
+pub const ANSWERS_NAME: &str = ".pgen-answers.yaml";
+
+/// Where the template of a generated project came from.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    /// Absolute path of a local template, otherwise the library name or git source given to gen
+    pub source: String,
+
+    /// Commit checked out when the template came from git
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+
+    /// Version from the template's metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// Hash of the template with everything it extends or includes merged in
+    pub hash: String,
+}
+
+/// Definitions used to generate a project, with where its template came from.
+///
+/// Provenance is kept under `_pgen` so the file can be read back as a definitions file.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Answers {
+    #[serde(rename = "_pgen", default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+
+    #[serde(flatten)]
+    pub defs: BTreeMap<String, Value>,
+}
+
+impl Provenance {
+    pub fn new(
+        source: String,
+        commit: Option<String>,
+        templ: &ProjectTemplate,
+    ) -> Result<Provenance, serde_yaml::Error> {
+        return Ok(Provenance {
+            source,
+            commit,
+            version: templ.metadata.as_ref().and_then(|m| m.version.clone()),
+            hash: template_hash(templ)?,
+        });
+    }
+}
+
+impl Answers {
+    /// Answers file to write into the generated project, never rendered so values stay as given.
+    pub fn to_file(&self) -> Result<RenderedFile, serde_yaml::Error> {
+        return Ok(RenderedFile {
+            content: serde_yaml::to_string(self)?.into_bytes(),
+            mode: None,
+        });
+    }
+
+    pub fn into_definitions(self) -> Definitions {
+        return self.defs.into_iter().collect();
+    }
+}
+
+/// FNV-1a hash of the template's yaml, stable across runs and platforms.
+fn template_hash(templ: &ProjectTemplate) -> Result<String, serde_yaml::Error> {
+    let yaml = serde_yaml::to_string(templ)?;
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in yaml.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    return Ok(format!("{:016x}", hash));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Answers, Provenance};
+    use crate::{
+        render::Value,
+        template::{ProjectTemplate, TemplateMetadata, Variable},
+    };
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn round_trip() {
+        let templ = ProjectTemplate {
+            metadata: Some(TemplateMetadata {
+                version: Some("1.2.0".to_string()),
+                ..TemplateMetadata::default()
+            }),
+            variables: vec![Variable::from("project_name")],
+            ..ProjectTemplate::default()
+        };
+
+        let provenance = |source: &str, templ: &ProjectTemplate| {
+            return Provenance::new(source.to_string(), None, templ)
+                .expect("Error hashing template");
+        };
+
+        let cmake_lib = provenance("cmake-lib", &templ);
+        assert!(cmake_lib.version.as_deref() == Some("1.2.0"));
+        assert!(cmake_lib.hash.len() == 16);
+        assert!(cmake_lib.hash == provenance("", &templ).hash);
+        assert!(cmake_lib.hash != provenance("", &ProjectTemplate::default()).hash);
+
+        let answers = Answers {
+            provenance: Some(cmake_lib),
+            defs: BTreeMap::from([
+                ("project_name".to_string(), "{@ demo @}".into()),
+                ("modules".to_string(), Value::List(vec!["core".to_string()])),
+            ]),
+        };
+        let file = answers.to_file().expect("Error writing answers");
+
+        let yaml = String::from_utf8(file.content).unwrap();
+        assert!(yaml.starts_with("_pgen:\n  source: cmake-lib\n  version: 1.2.0\n"));
+
+        let read: Answers = serde_yaml::from_str(&yaml).expect("Error reading answers");
+        assert!(read.provenance == answers.provenance);
+
+        let defs = read.into_definitions();
+        assert!(defs.len() == 2);
+        assert!(defs["project_name"] == "{@ demo @}".into());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::template::{FileMode, RenderedTemplate};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// How a dry run prints the planned project.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlanFormat {
+    /// Tree of directories and files
+    #[default]
+    Text,
+    /// Json for scripts
+    Json,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub size: usize,
+    pub mode: Option<FileMode>,
+    /// A file already exists at the path
+    pub exists: bool,
+}
+
+/// Everything a generation would write, without writing it.
+#[derive(Serialize, Debug)]
+pub struct Plan {
+    pub root: PathBuf,
+    pub directories: Vec<PathBuf>,
+    pub files: Vec<PlannedFile>,
+    pub unresolved: Vec<String>,
+}
+
+#[derive(Default)]
+struct TreeNode {
+    children: BTreeMap<String, TreeNode>,
+    file: Option<(usize, bool)>,
+}
+
+impl Plan {
+    pub fn new(root: &Path, templ: &RenderedTemplate, unresolved: Vec<String>) -> Plan {
+        let mut directories: Vec<PathBuf> = templ
+            .directories
+            .iter()
+            .filter(|d| !d.as_os_str().is_empty())
+            .cloned()
+            .collect();
+        directories.sort();
+
+        let mut files: Vec<PlannedFile> = templ
+            .files
+            .iter()
+            .map(|(p, f)| PlannedFile {
+                path: p.to_path_buf(),
+                size: f.content.len(),
+                mode: f.mode,
+                exists: root.join(p).exists(),
+            })
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        return Plan {
+            root: root.to_path_buf(),
+            directories,
+            files,
+            unresolved,
+        };
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut tree = TreeNode::default();
+        for dir in &self.directories {
+            insert(&mut tree, dir);
+        }
+        for file in &self.files {
+            insert(&mut tree, &file.path).file = Some((file.size, file.exists));
+        }
+
+        let mut out = format!("{}/\n", self.root.display());
+        write_tree(&tree, "", &mut out);
+
+        if !self.unresolved.is_empty() {
+            out.push_str(&format!(
+                "\nUnresolved variables: {}\n",
+                self.unresolved.join(", ")
+            ));
+        }
+
+        return out;
+    }
+}
+
+fn insert<'a>(tree: &'a mut TreeNode, path: &Path) -> &'a mut TreeNode {
+    let mut node = tree;
+    for component in path.iter() {
+        node = node
+            .children
+            .entry(component.to_string_lossy().to_string())
+            .or_default();
+    }
+
+    return node;
+}
+
+fn write_tree(node: &TreeNode, prefix: &str, out: &mut String) {
+    let count = node.children.len();
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let last = i + 1 == count;
+        let branch = if last { "└── " } else { "├── " };
+
+        match child.file {
+            Some((size, exists)) => out.push_str(&format!(
+                "{}{}{} ({} B){}\n",
+                prefix,
+                branch,
+                name,
+                size,
+                if exists { " exists" } else { "" }
+            )),
+            None => out.push_str(&format!("{}{}{}/\n", prefix, branch, name)),
+        }
+
+        let indent = if last { "    " } else { "│   " };
+        write_tree(child, &format!("{}{}", prefix, indent), out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Plan;
+    use crate::template::{FileMode, RenderedFile, RenderedTemplate};
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    fn plan() -> Plan {
+        let templ = RenderedTemplate {
+            directories: vec![PathBuf::from(""), PathBuf::from("src/io")],
+            files: HashMap::from([
+                (
+                    PathBuf::from("CMakeLists.txt"),
+                    RenderedFile {
+                        content: b"project(fruit)".to_vec(),
+                        mode: None,
+                    },
+                ),
+                (
+                    PathBuf::from("src/main.cpp"),
+                    RenderedFile {
+                        content: b"{@ namespace @}".to_vec(),
+                        mode: Some(FileMode(0o755)),
+                    },
+                ),
+            ]),
+        };
+
+        return Plan::new(
+            Path::new("does/not/exist"),
+            &templ,
+            vec!["namespace".to_string()],
+        );
+    }
+
+    #[test]
+    fn text() {
+        let expected = "does/not/exist/
+├── CMakeLists.txt (14 B)
+└── src/
+    ├── io/
+    └── main.cpp (15 B)
+
+Unresolved variables: namespace
+";
+
+        assert!(plan().to_text() == expected);
+    }
+
+    #[test]
+    fn json() {
+        let json = serde_json::to_value(plan()).expect("Error serializing plan");
+
+        assert!(json["directories"] == serde_json::json!(["src/io"]));
+        assert!(json["files"][1]["path"] == "src/main.cpp");
+        assert!(json["files"][1]["size"] == 15);
+        assert!(json["files"][1]["mode"] == "755");
+        assert!(json["files"][1]["exists"] == false);
+        assert!(json["unresolved"] == serde_json::json!(["namespace"]));
+    }
+}
@@ -0,0 +1,361 @@
+use crate::{
+    git::{GitError, GitSource},
+    library::{Library, LibraryError},
+    template::{load_template, TemplateError},
+};
+use std::path::{Component, Path, PathBuf};
+use tempfile::TempDir;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum InstallError {
+    #[error("Unable to open template library")]
+    LibraryError(#[source] LibraryError),
+
+    #[error("Error opening template file")]
+    TemplateFileError(PathBuf),
+
+    #[error("Unable to read template")]
+    TemplateReadError(#[source] TemplateError),
+
+    #[error("Unable to name template from its source, give it a name")]
+    NameError(String),
+
+    #[error("Template names must be a single file name not starting with '.'")]
+    InvalidNameError(String),
+
+    #[error("A template with this name is already installed")]
+    TemplateExistsError(String),
+
+    #[error("Unable to fetch template from git")]
+    GitError(#[source] GitError),
+
+    #[error("Error copying template into the library")]
+    CopyError(PathBuf),
+}
+
+/// Copy the template at `source`, a path or a git url, into the template library.
+///
+/// The template is named `name`, or after its file, directory or repository. The source is
+/// recorded with it so it can be installed again to update it.
+pub fn install(source: &str, name: Option<&str>, force: bool) -> Result<(), InstallError> {
+    let lib = match Library::open() {
+        Ok(l) => l,
+        Err(e) => return Err(InstallError::LibraryError(e)),
+    };
+
+    let (name, dest) = install_into(&lib, source, name, force)?;
+    println!("Installed {} to {}", name, dest.display());
+
+    return Ok(());
+}
+
+fn install_into(
+    lib: &Library,
+    source: &str,
+    name: Option<&str>,
+    force: bool,
+) -> Result<(String, PathBuf), InstallError> {
+    let name = match name {
+        Some(n) => n.to_string(),
+        None => default_name(source)?,
+    };
+
+    // The name is joined to the install directory, so it must not lead anywhere else
+    if !is_valid_name(&name) {
+        return Err(InstallError::InvalidNameError(name));
+    }
+
+    // Git repositories are checked out, then installed like any other template
+    let (path, recorded) = if let Some(git_source) = GitSource::parse(source) {
+        match git_source.checkout() {
+            Ok(c) => (c.path, source.to_string()),
+            Err(e) => return Err(InstallError::GitError(e)),
+        }
+    } else {
+        let Ok(path) = Path::new(source).canonicalize() else {
+            return Err(InstallError::TemplateFileError(PathBuf::from(source)));
+        };
+        let recorded = path.display().to_string();
+        (path, recorded)
+    };
+
+    // Only install templates that load
+    let templ = match load_template(&path) {
+        Ok(t) => t,
+        Err(TemplateError::FileError(p)) => return Err(InstallError::TemplateFileError(p)),
+        Err(e) => return Err(InstallError::TemplateReadError(e)),
+    };
+
+    let existing: Vec<PathBuf> = [
+        name.clone(),
+        format!("{}.yaml", name),
+        format!("{}.yml", name),
+    ]
+    .iter()
+    .map(|n| lib.install_dir.join(n))
+    .filter(|p| p.exists())
+    .collect();
+    if !existing.is_empty() && !force {
+        return Err(InstallError::TemplateExistsError(name));
+    }
+
+    if std::fs::create_dir_all(&lib.install_dir).is_err() {
+        return Err(InstallError::CopyError(lib.install_dir.clone()));
+    }
+
+    // The new copy is made in a hidden directory inside the library, so an installed template is
+    // only replaced once its replacement is complete
+    let staging = match tempfile::Builder::new()
+        .prefix(".pgen-")
+        .tempdir_in(&lib.install_dir)
+    {
+        Ok(s) => s,
+        Err(_) => return Err(InstallError::CopyError(lib.install_dir.clone())),
+    };
+
+    // Directories are copied as they are, files are stored with what they extend merged in so
+    // relative paths to other templates don't break
+    let file_name = match path.is_dir() {
+        true => name.clone(),
+        false => format!("{}.yaml", name),
+    };
+    let staged = staging.path().join(&file_name);
+    if path.is_dir() {
+        copy_dir(&path, &staged)?;
+    } else {
+        let written = serde_yaml::to_string(&templ)
+            .ok()
+            .and_then(|yaml| std::fs::write(&staged, yaml).ok());
+        if written.is_none() {
+            return Err(InstallError::CopyError(staged));
+        }
+    }
+
+    // Old copies are moved aside and removed with the staging directory
+    let old = staging.path().join("old");
+    if std::fs::create_dir(&old).is_err() {
+        return Err(InstallError::CopyError(old));
+    }
+    let mut moved = Vec::new();
+    for p in existing {
+        let aside = old.join(p.file_name().unwrap_or_default());
+        if std::fs::rename(&p, &aside).is_err() {
+            restore(&moved, staging);
+            return Err(InstallError::CopyError(p));
+        }
+        moved.push((p, aside));
+    }
+
+    let dest = lib.install_dir.join(&file_name);
+    if std::fs::rename(&staged, &dest).is_err() {
+        restore(&moved, staging);
+        return Err(InstallError::CopyError(dest));
+    }
+
+    if let Err(e) = lib.record_source(&name, &recorded) {
+        return Err(InstallError::LibraryError(e));
+    }
+
+    return Ok((name, dest));
+}
+
+/// Put old copies of a template back after its replacement failed, keeping `staging` if any of them
+/// can't be moved back so they aren't removed with it.
+fn restore(moved: &[(PathBuf, PathBuf)], staging: TempDir) {
+    let restored = moved
+        .iter()
+        .rev()
+        .all(|(p, aside)| std::fs::rename(aside, p).is_ok());
+    if !restored {
+        let _ = staging.keep();
+    }
+}
+
+/// Name of a template from the last part of its path or url, without an extension.
+fn default_name(source: &str) -> Result<String, InstallError> {
+    // Git sources are named after their subpath or repository
+    let source = match GitSource::parse(source) {
+        Some(GitSource {
+            subpath: Some(sub), ..
+        }) => sub,
+        Some(GitSource { url, .. }) => url,
+        None => source.to_string(),
+    };
+
+    let last = source
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\', ':'])
+        .next()
+        .unwrap_or("");
+    let name = match last.rsplit_once('.') {
+        Some((stem, "yaml" | "yml" | "git")) => stem,
+        _ => last,
+    };
+
+    if name.is_empty() || name.starts_with('.') {
+        return Err(InstallError::NameError(source));
+    }
+
+    return Ok(name.to_string());
+}
+
+/// Whether `name` is a single path component that isn't hidden.
+fn is_valid_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+
+    return match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => !name.starts_with('.') && !name.contains(['/', '\\']),
+        _ => false,
+    };
+}
+
+/// Copy a directory recursively, leaving out git metadata.
+fn copy_dir(src: &Path, dest: &Path) -> Result<(), InstallError> {
+    if std::fs::create_dir_all(dest).is_err() {
+        return Err(InstallError::CopyError(dest.to_path_buf()));
+    }
+
+    let Ok(entries) = std::fs::read_dir(src) else {
+        return Err(InstallError::CopyError(src.to_path_buf()));
+    };
+    for entry in entries {
+        let Ok(entry) = entry else {
+            return Err(InstallError::CopyError(src.to_path_buf()));
+        };
+        if entry.file_name() == ".git" {
+            continue;
+        }
+
+        let (from, to) = (entry.path(), dest.join(entry.file_name()));
+        if from.is_dir() {
+            copy_dir(&from, &to)?;
+        } else if std::fs::copy(&from, &to).is_err() {
+            return Err(InstallError::CopyError(from));
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_name, install_into, is_valid_name, InstallError};
+    use crate::library::Library;
+
+    #[test]
+    fn names() {
+        assert!(default_name("templates/cmake-lib.yaml").unwrap() == "cmake-lib");
+        assert!(default_name("templates/cmake-lib/").unwrap() == "cmake-lib");
+        assert!(default_name("file:///srv/git/cli.git").unwrap() == "cli");
+        assert!(default_name("git@example.com:team/cli.git").unwrap() == "cli");
+        assert!(default_name("file:///srv/git/templates.git//cpp/lib#v1").unwrap() == "lib");
+        assert!(default_name("..").is_err());
+    }
+
+    #[test]
+    fn invalid_names() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let lib =
+            Library::with_dirs(&dir.path().join("data"), None).expect("Error opening library");
+        let victim = dir.path().join("victim");
+        std::fs::create_dir(&victim).expect("Error creating dir");
+        let src = dir.path().join("t.yaml");
+        std::fs::write(&src, "files: []\n").expect("Error writing");
+        let src = src.display().to_string();
+
+        for name in [
+            victim.display().to_string(),
+            "../../victim".to_string(),
+            "a/b".to_string(),
+            ".hidden".to_string(),
+            "..".to_string(),
+            "".to_string(),
+        ] {
+            assert!(matches!(
+                install_into(&lib, &src, Some(&name), true),
+                Err(InstallError::InvalidNameError(_))
+            ));
+        }
+
+        assert!(victim.exists());
+        assert!(!lib.install_dir.exists());
+        assert!(is_valid_name("cmake-lib"));
+    }
+
+    #[test]
+    fn install_file_and_dir() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let lib =
+            Library::with_dirs(&dir.path().join("data"), None).expect("Error opening library");
+
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("lib/src")).expect("Error creating dir");
+        std::fs::write(src.join("base.yaml"), "variables: [project_name]\n")
+            .expect("Error writing");
+        std::fs::write(src.join("cli.yaml"), "extends: base.yaml\n").expect("Error writing");
+        std::fs::write(src.join("lib/src/lib.cpp"), "{@ project_name @}").expect("Error writing");
+
+        let cli = src.join("cli.yaml").display().to_string();
+        let (name, dest) = install_into(&lib, &cli, None, false).expect("Error installing");
+        assert!(name == "cli");
+
+        // What the file extends is merged in
+        let installed = std::fs::read_to_string(&dest).expect("Error reading installed template");
+        assert!(installed.contains("project_name") && !installed.contains("extends"));
+
+        let lib_src = src.join("lib").display().to_string();
+        install_into(&lib, &lib_src, Some("cmake-lib"), false).expect("Error installing");
+        assert!(lib
+            .find("cmake-lib")
+            .is_some_and(|p| p.join("src/lib.cpp").exists()));
+
+        assert!(matches!(
+            install_into(&lib, &lib_src, Some("cmake-lib"), false),
+            Err(InstallError::TemplateExistsError(_))
+        ));
+        install_into(&lib, &cli, Some("cmake-lib"), true).expect("Error reinstalling");
+        assert!(lib.find("cmake-lib").is_some_and(|p| p.is_file()));
+        assert!(!lib.install_dir.join("cmake-lib").exists());
+
+        let sources = lib.sources().expect("Error reading sources");
+        assert!(
+            sources["cli"]
+                == src
+                    .join("cli.yaml")
+                    .canonicalize()
+                    .unwrap()
+                    .display()
+                    .to_string()
+        );
+        assert!(sources["cmake-lib"] == sources["cli"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_reinstall_keeps_old_copy() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let lib =
+            Library::with_dirs(&dir.path().join("data"), None).expect("Error opening library");
+
+        let src = dir.path().join("lib");
+        std::fs::create_dir(&src).expect("Error creating dir");
+        std::fs::write(src.join("lib.cpp"), "{@ project_name @}").expect("Error writing");
+        let src = src.display().to_string();
+        install_into(&lib, &src, Some("cmake-lib"), false).expect("Error installing");
+
+        // A dangling link can't be copied
+        let broken = dir.path().join("broken");
+        std::fs::create_dir(&broken).expect("Error creating dir");
+        std::os::unix::fs::symlink(dir.path().join("missing"), broken.join("link"))
+            .expect("Error creating link");
+        let broken = broken.display().to_string();
+
+        assert!(matches!(
+            install_into(&lib, &broken, Some("cmake-lib"), true),
+            Err(InstallError::CopyError(_))
+        ));
+        assert!(lib.install_dir.join("cmake-lib/lib.cpp").exists());
+        assert!(std::fs::read_dir(&lib.install_dir).unwrap().count() == 1);
+    }
+}
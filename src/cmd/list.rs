@@ -0,0 +1,93 @@
+use crate::{
+    library::{Library, LibraryError},
+    template::load_template,
+};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ListError {
+    #[error("Unable to open template library")]
+    LibraryError(#[source] LibraryError),
+}
+
+/// List every template in the template library with its description and variables.
+pub fn list() -> Result<(), ListError> {
+    let lib = match Library::open() {
+        Ok(l) => l,
+        Err(e) => return Err(ListError::LibraryError(e)),
+    };
+    let sources = match lib.sources() {
+        Ok(s) => s,
+        Err(e) => return Err(ListError::LibraryError(e)),
+    };
+
+    let templates = lib.templates();
+    if templates.is_empty() {
+        println!("No templates in {}", lib.install_dir.display());
+    }
+
+    for (name, path) in templates {
+        print!(
+            "{}",
+            describe_template(&name, &path, sources.get(&name).map(|s| s.as_str()))
+        );
+    }
+
+    return Ok(());
+}
+
+fn describe_template(name: &str, path: &Path, source: Option<&str>) -> String {
+    let mut out = name.to_string();
+
+    match load_template(path) {
+        Ok(templ) => {
+            if let Some(desc) = templ.metadata.and_then(|m| m.description) {
+                out.push_str(&format!(" - {}", desc));
+            }
+            out.push('\n');
+
+            if !templ.variables.is_empty() {
+                let names: Vec<String> = templ.variables.into_iter().map(|v| v.name).collect();
+                out.push_str(&format!("    variables: {}\n", names.join(", ")));
+            }
+        }
+        Err(e) => out.push_str(&format!("\n    unable to read template: {:?}\n", e)),
+    }
+
+    out.push_str(&format!("    path: {}\n", path.display()));
+    if let Some(s) = source {
+        out.push_str(&format!("    installed from: {}\n", s));
+    }
+
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::describe_template;
+
+    #[test]
+    fn describe() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("cli.yaml");
+        std::fs::write(
+            &path,
+            "metadata:\n  description: Command line tool\nvariables: [project_name, namespace]\n",
+        )
+        .expect("Error writing template");
+
+        assert!(
+            describe_template("cli", &path, Some("/home/ana/cli.yaml"))
+                == format!(
+                    "cli - Command line tool\n    variables: project_name, namespace\n    path: {}\n    installed from: /home/ana/cli.yaml\n",
+                    path.display()
+                )
+        );
+
+        std::fs::write(&path, "files: 3\n").expect("Error writing template");
+        assert!(
+            describe_template("cli", &path, None).starts_with("cli\n    unable to read template: ")
+        );
+    }
+}
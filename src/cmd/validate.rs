@@ -0,0 +1,470 @@
+use crate::{
+    cmd::fd::{get_loop_vars, get_template_vars, get_vars_from_string},
+    paths::escapes_root,
+    render::{check_syntax, RenderError, Value},
+    template::{load_template, normalize_path, ProjectTemplate, TemplateError},
+};
+use regex::Regex;
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("Error opening template file")]
+    TemplateFileError(PathBuf),
+
+    #[error("Unable to read template")]
+    TemplateReadError(#[source] TemplateError),
+
+    #[error("Template has problems")]
+    ProblemsFound(usize),
+}
+
+/// A problem found in a template, and where it is.
+#[derive(Debug, PartialEq, Eq)]
+struct Problem {
+    location: String,
+    message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}: {}", self.location, self.message);
+    }
+}
+
+fn problem(location: &str, message: String) -> Problem {
+    return Problem {
+        location: location.to_string(),
+        message,
+    };
+}
+
+fn pattern_problem(name: &str, pattern: &str) -> Problem {
+    return problem(
+        &format!("variable {}", name),
+        format!("pattern '{}' is not a valid regex", pattern),
+    );
+}
+
+/// Text in a template that can hold placeholders.
+struct Source<'a> {
+    location: String,
+    text: &'a str,
+    is_path: bool,
+}
+
+/// Check the template at `templ_path` and print every problem found.
+///
+/// Returns an error if there were any problems, so scripts can fail on them.
+pub fn validate(templ_path: &Path) -> Result<(), ValidationError> {
+    let templ = match load_template(templ_path) {
+        Ok(t) => t,
+        Err(TemplateError::FileError(p)) => return Err(ValidationError::TemplateFileError(p)),
+        Err(TemplateError::InvalidPattern(name, pattern)) => {
+            println!("{}", pattern_problem(&name, &pattern));
+            return Err(ValidationError::ProblemsFound(1));
+        }
+        Err(e) => return Err(ValidationError::TemplateReadError(e)),
+    };
+
+    let problems = find_problems(&templ);
+    for p in &problems {
+        println!("{}", p);
+    }
+
+    if !problems.is_empty() {
+        return Err(ValidationError::ProblemsFound(problems.len()));
+    }
+
+    println!("No problems found");
+    return Ok(());
+}
+
+fn find_problems(templ: &ProjectTemplate) -> Vec<Problem> {
+    let mut problems = check_variables(templ);
+
+    let mut names: HashSet<String> = get_template_vars(&templ.directories, &templ.files);
+    names.extend(templ.variables.iter().map(|v| v.name.clone()));
+    names.extend(templ.hooks.commands().flat_map(|c| get_vars_from_string(c)));
+    for source in sources(templ) {
+        problems.extend(check_delimiters(&source, &names));
+    }
+
+    problems.extend(check_paths(templ));
+
+    return problems;
+}
+
+fn default_items(default: &Option<Value>) -> Vec<&str> {
+    return match default {
+        Some(Value::Scalar(s)) => vec![s.as_str()],
+        Some(Value::List(l)) => l.iter().map(|s| s.as_str()).collect(),
+        None => vec![],
+    };
+}
+
+fn sources(templ: &ProjectTemplate) -> Vec<Source<'_>> {
+    let mut sources = Vec::new();
+
+    for var in &templ.variables {
+        for item in default_items(&var.default) {
+            sources.push(Source {
+                location: format!("variable {}", var.name),
+                text: item,
+                is_path: false,
+            });
+        }
+    }
+
+    for dir in &templ.directories {
+        sources.push(Source {
+            location: format!("directory {}", dir),
+            text: dir,
+            is_path: true,
+        });
+    }
+
+    for file in &templ.files {
+        sources.push(Source {
+            location: format!("path {}", file.path),
+            text: &file.path,
+            is_path: true,
+        });
+
+        if file.encoding.is_none() {
+            sources.push(Source {
+                location: format!("file {}", file.path),
+                text: &file.content,
+                is_path: false,
+            });
+        }
+    }
+
+    for command in templ.hooks.commands() {
+        sources.push(Source {
+            location: format!("hook {}", command),
+            text: command,
+            is_path: false,
+        });
+    }
+
+    return sources;
+}
+
+/// Variables used but not declared, and declared but never used.
+fn check_variables(templ: &ProjectTemplate) -> Vec<Problem> {
+    let mut used: HashSet<String> = get_template_vars(&templ.directories, &templ.files);
+    for var in &templ.variables {
+        for item in default_items(&var.default) {
+            used.extend(get_vars_from_string(item));
+        }
+    }
+    for command in templ.hooks.commands() {
+        used.extend(get_vars_from_string(command));
+    }
+
+    let declared: HashSet<&String> = templ.variables.iter().map(|v| &v.name).collect();
+
+    let mut undeclared: Vec<&String> = used.iter().filter(|v| !declared.contains(v)).collect();
+    undeclared.sort();
+
+    let mut problems: Vec<Problem> = undeclared
+        .into_iter()
+        .map(|v| problem("variables", format!("'{}' is used but not declared", v)))
+        .collect();
+
+    for var in &templ.variables {
+        if let Err(TemplateError::InvalidPattern(name, pattern)) = var.check() {
+            problems.push(pattern_problem(&name, &pattern));
+        }
+        if !used.contains(&var.name) {
+            problems.push(problem(
+                &format!("variable {}", var.name),
+                "declared but never used".to_string(),
+            ));
+        }
+    }
+
+    return problems;
+}
+
+/// Tags that don't parse, delimiters without a partner, and placeholders in the wrong syntax.
+fn check_delimiters(source: &Source, names: &HashSet<String>) -> Vec<Problem> {
+    let tag_re = Regex::new(r"\{@(?P<inner>.*?)@\}").expect("Error compiling regex");
+    let known_re = Regex::new(r"^(\w+(\s*\|.*)?|if\s+\w+|for\s+\w+\s+in\s+\w+|else|end)$")
+        .expect("Error compiling regex");
+    let brace_re = Regex::new(r"\{\s*(?P<var>\w+)\s*\}").expect("Error compiling regex");
+    let text = source.text;
+
+    // Paths are a single line, content is located by line
+    let location = |offset: usize| -> String {
+        if source.is_path {
+            return source.location.clone();
+        }
+        return format!(
+            "{}:{}",
+            source.location,
+            text[..offset].matches('\n').count() + 1
+        );
+    };
+
+    let mut problems = Vec::new();
+
+    if let Err(e) = check_syntax(text) {
+        let message = match e {
+            RenderError::UnexpectedTag(t) => format!("'{{@ {} @}}' without an opening block", t),
+            RenderError::UnclosedBlock(t) => format!("'{}' is never closed", t),
+            RenderError::InvalidFilter(f) => format!("unknown filter '{}'", f),
+            RenderError::InvalidBinaryContent(e) => e,
+            RenderError::UnresolvedDefault(_, r) => format!("'{}' has no value", r),
+        };
+        problems.push(problem(&source.location, message));
+    }
+
+    for m in tag_re.captures_iter(text) {
+        if !known_re.is_match(m["inner"].trim()) {
+            let whole = m.get(0).expect("Capture without match");
+            problems.push(problem(
+                &location(whole.start()),
+                format!("unrecognized tag '{}'", whole.as_str()),
+            ));
+        }
+    }
+
+    // Whatever is left once whole tags are removed has no partner
+    let mut pos = 0;
+    let mut rest = Vec::new();
+    for m in tag_re.find_iter(text) {
+        rest.push((pos, &text[pos..m.start()]));
+        pos = m.end();
+    }
+    rest.push((pos, &text[pos..]));
+
+    for (start, part) in rest {
+        for (i, _) in part.match_indices("{@") {
+            problems.push(problem(
+                &location(start + i),
+                "'{@' without a closing '@}'".to_string(),
+            ));
+        }
+        for (i, _) in part.match_indices("@}") {
+            problems.push(problem(
+                &location(start + i),
+                "'@}' without an opening '{@'".to_string(),
+            ));
+        }
+    }
+
+    // Braces in paths are almost always a mistake, in content only flag known variables
+    for m in brace_re.captures_iter(text) {
+        let var = &m["var"];
+        if source.is_path || names.contains(var) {
+            let whole = m.get(0).expect("Capture without match");
+            problems.push(problem(
+                &location(whole.start()),
+                format!(
+                    "'{}' looks like a placeholder, use '{{@ {} @}}'",
+                    whole.as_str(),
+                    var
+                ),
+            ));
+        }
+    }
+
+    return problems;
+}
+
+/// Paths listed twice, files outside of the listed directories, and paths that escape the root.
+fn check_paths(templ: &ProjectTemplate) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    let dirs: Vec<String> = templ
+        .directories
+        .iter()
+        .map(|d| normalize_path(d).trim_end_matches('/').to_string())
+        .collect();
+    let mut seen = HashSet::new();
+    for (dir, raw) in dirs.iter().zip(&templ.directories) {
+        let location = format!("directory {}", raw);
+        if !seen.insert(dir) {
+            problems.push(problem(&location, "listed more than once".to_string()));
+        }
+        if escapes_root(Path::new(dir)) {
+            problems.push(problem(&location, "escapes the project root".to_string()));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for file in &templ.files {
+        let location = format!("path {}", file.path);
+        let path = normalize_path(&file.path);
+        if !seen.insert(path.clone()) {
+            problems.push(problem(&location, "listed more than once".to_string()));
+        }
+        if escapes_root(Path::new(&path)) {
+            problems.push(problem(&location, "escapes the project root".to_string()));
+        }
+
+        // A path with a loop can't be split into its directories before rendering
+        if !get_loop_vars(&path).is_empty() {
+            continue;
+        }
+        if let Some((parent, _)) = path.rsplit_once('/') {
+            if !dirs.iter().any(|d| d == parent) {
+                problems.push(problem(
+                    &location,
+                    format!("parent directory '{}' is not listed in directories", parent),
+                ));
+            }
+        }
+    }
+
+    return problems;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_problems, Problem};
+    use crate::template::{Hooks, ProjectTemplate, TemplateFile, Variable};
+
+    fn file(path: &str, content: &str) -> TemplateFile {
+        return TemplateFile {
+            path: path.to_string(),
+            content: content.to_string(),
+            encoding: None,
+            mode: None,
+        };
+    }
+
+    fn problems(templ: &ProjectTemplate) -> Vec<String> {
+        return find_problems(templ)
+            .iter()
+            .map(Problem::to_string)
+            .collect();
+    }
+
+    #[test]
+    fn valid_template() {
+        let templ = ProjectTemplate {
+            hooks: Hooks {
+                pre_gen: vec![],
+                post_gen: vec!["git remote add origin {@ remote @}".to_string()],
+            },
+            variables: vec![
+                Variable::from("project_name"),
+                Variable {
+                    default: Some("{@ project_name | upper @}".into()),
+                    ..Variable::from("namespace")
+                },
+                Variable::from("modules"),
+                Variable::from("remote"),
+            ],
+            directories: vec!["".to_string(), "src".to_string()],
+            files: vec![
+                file(
+                    "src/main.cpp",
+                    "{@ if namespace @}\nnamespace {@ namespace @} {}\n{@ end @}\nint main() { return 0; }",
+                ),
+                file("src/{@ for m in modules @}{@ m @}.cpp{@ end @}", "{@ m @}"),
+                file("README.md", "{@ project_name @}"),
+            ],
+            ..ProjectTemplate::default()
+        };
+
+        assert!(problems(&templ).is_empty());
+    }
+
+    #[test]
+    fn variable_problems() {
+        let templ = ProjectTemplate {
+            variables: vec![Variable::from("project_name"), Variable::from("unused")],
+            directories: vec![],
+            files: vec![file("README.md", "{@ project_name @} {@ version @}")],
+            ..ProjectTemplate::default()
+        };
+
+        assert!(
+            problems(&templ)
+                == vec![
+                    "variables: 'version' is used but not declared",
+                    "variable unused: declared but never used",
+                ]
+        );
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let templ = ProjectTemplate {
+            variables: vec![Variable {
+                pattern: Some("[a-z".to_string()),
+                ..Variable::from("project_name")
+            }],
+            directories: vec![],
+            files: vec![file("README.md", "{@ project_name @}")],
+            ..ProjectTemplate::default()
+        };
+
+        assert!(
+            problems(&templ) == vec!["variable project_name: pattern '[a-z' is not a valid regex"]
+        );
+    }
+
+    #[test]
+    fn malformed_delimiters() {
+        let templ = ProjectTemplate {
+            variables: vec![Variable::from("project_name")],
+            directories: vec!["include".to_string()],
+            files: vec![
+                file("include/{project_name}.hpp", "{@ project_name @}"),
+                file(
+                    "README.md",
+                    "# { project_name }\n{@ project name @}\n{@ project_name\n{@ if project_name @}",
+                ),
+            ],
+            ..ProjectTemplate::default()
+        };
+
+        assert!(
+            problems(&templ)
+                == vec![
+                    "path include/{project_name}.hpp: '{project_name}' looks like a placeholder, use '{@ project_name @}'",
+                    "file README.md: '{@ if project_name @}' is never closed",
+                    "file README.md:2: unrecognized tag '{@ project name @}'",
+                    "file README.md:3: '{@' without a closing '@}'",
+                    "file README.md:1: '{ project_name }' looks like a placeholder, use '{@ project_name @}'",
+                ]
+        );
+    }
+
+    #[test]
+    fn path_problems() {
+        let templ = ProjectTemplate {
+            variables: vec![],
+            directories: vec!["src".to_string(), "src/".to_string(), "../out".to_string()],
+            files: vec![
+                file("src/main.cpp", ""),
+                file("src\\main.cpp", ""),
+                file("include/lib.hpp", ""),
+                file("/etc/passwd", ""),
+            ],
+            ..ProjectTemplate::default()
+        };
+
+        assert!(
+            problems(&templ)
+                == vec![
+                    "directory src/: listed more than once",
+                    "directory ../out: escapes the project root",
+                    "path src\\main.cpp: listed more than once",
+                    "path include/lib.hpp: parent directory 'include' is not listed in directories",
+                    "path /etc/passwd: escapes the project root",
+                    "path /etc/passwd: parent directory '/etc' is not listed in directories",
+                ]
+        );
+    }
+}
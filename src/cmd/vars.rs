@@ -0,0 +1,380 @@
+use crate::{
+    cmd::fd::{get_loop_vars, get_template_vars, get_vars_from_string},
+    render::Value,
+    template::{load_template, ProjectTemplate, TemplateError, TemplateMetadata, VariableType},
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum VariablesError {
+    #[error("Error opening template file")]
+    TemplateFileError(PathBuf),
+
+    #[error("Unable to read template")]
+    TemplateReadError(#[source] TemplateError),
+
+    #[error("File already exists at output")]
+    OutputAlreadyExists(PathBuf),
+
+    #[error("Error writing definitions file")]
+    DefsWriteError(PathBuf),
+
+    #[error("Error generating yaml")]
+    SerializationError(String),
+}
+
+/// Where in a template a variable is referenced.
+#[derive(Debug, PartialEq, Eq)]
+enum Usage {
+    Directory(String),
+    Path(String),
+    Content(String, usize),
+    Default(String),
+    Hook(String),
+}
+
+/// List the variables of the template at `templ_path`, where each one is used, and whether the
+/// template's `variables` list matches what it references.
+///
+/// If `emit_defs` is given, a definitions file to fill in is written there as well.
+pub fn vars(
+    templ_path: &Path,
+    emit_defs: Option<&Path>,
+    force: bool,
+) -> Result<(), VariablesError> {
+    let templ = match load_template(templ_path) {
+        Ok(t) => t,
+        Err(TemplateError::FileError(p)) => return Err(VariablesError::TemplateFileError(p)),
+        Err(e) => return Err(VariablesError::TemplateReadError(e)),
+    };
+
+    if let Some(metadata) = &templ.metadata {
+        println!("{}", describe_metadata(metadata));
+    }
+    print!("{}", describe_vars(&templ));
+
+    if let Some(output) = emit_defs {
+        if output.exists() && !force {
+            return Err(VariablesError::OutputAlreadyExists(output.to_path_buf()));
+        }
+
+        if std::fs::write(output, skeleton_defs(&templ)?).is_err() {
+            return Err(VariablesError::DefsWriteError(output.to_path_buf()));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Names of every variable in the template, declared ones in order followed by the rest sorted.
+fn var_names(templ: &ProjectTemplate) -> Vec<String> {
+    let mut names: Vec<String> = templ.variables.iter().map(|v| v.name.clone()).collect();
+
+    let mut undeclared: Vec<String> = get_template_vars(&templ.directories, &templ.files)
+        .into_iter()
+        .chain(templ.hooks.commands().flat_map(|c| get_vars_from_string(c)))
+        .filter(|v| !names.contains(v))
+        .collect();
+    undeclared.sort();
+    names.extend(undeclared);
+
+    return names;
+}
+
+/// Every place each variable is referenced, in template order.
+fn find_usages(templ: &ProjectTemplate) -> HashMap<String, Vec<Usage>> {
+    let mut usages: HashMap<String, Vec<Usage>> = HashMap::new();
+    let mut add = |vars: HashSet<String>, usage: &dyn Fn() -> Usage| {
+        let mut vars: Vec<String> = vars.into_iter().collect();
+        vars.sort();
+        for var in vars {
+            usages.entry(var).or_default().push(usage());
+        }
+    };
+
+    for var in &templ.variables {
+        let items = match &var.default {
+            Some(Value::Scalar(s)) => vec![s.clone()],
+            Some(Value::List(l)) => l.clone(),
+            None => vec![],
+        };
+        for item in items {
+            add(get_vars_from_string(&item), &|| {
+                return Usage::Default(var.name.clone());
+            });
+        }
+    }
+
+    for dir in &templ.directories {
+        add(get_vars_from_string(dir), &|| {
+            return Usage::Directory(dir.clone());
+        });
+    }
+
+    for file in &templ.files {
+        add(get_vars_from_string(&file.path), &|| {
+            return Usage::Path(file.path.clone());
+        });
+
+        if file.encoding.is_some() {
+            continue;
+        }
+
+        // Loop variables only show up once the whole content is seen, so filter each line by the
+        // variables of the whole file
+        let mut content_vars = get_vars_from_string(&file.content);
+        for item in get_loop_vars(&file.path) {
+            content_vars.remove(&item);
+        }
+
+        for (i, line) in file.content.lines().enumerate() {
+            let vars = get_vars_from_string(line)
+                .into_iter()
+                .filter(|v| content_vars.contains(v))
+                .collect();
+            add(vars, &|| {
+                return Usage::Content(file.path.clone(), i + 1);
+            });
+        }
+    }
+
+    for command in templ.hooks.commands() {
+        add(get_vars_from_string(command), &|| {
+            return Usage::Hook(command.clone());
+        });
+    }
+
+    return usages;
+}
+
+fn describe_metadata(metadata: &TemplateMetadata) -> String {
+    let mut out = String::new();
+
+    let title = [&metadata.name, &metadata.version]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<String>>()
+        .join(" ");
+    if !title.is_empty() {
+        out.push_str(&format!("{}\n", title));
+    }
+    if let Some(desc) = &metadata.description {
+        out.push_str(&format!("{}\n", desc));
+    }
+    if !metadata.authors.is_empty() {
+        out.push_str(&format!("Authors: {}\n", metadata.authors.join(", ")));
+    }
+    if let Some(min) = &metadata.min_pgen_version {
+        out.push_str(&format!("Needs pgen {} or newer\n", min));
+    }
+
+    return out;
+}
+
+fn describe_vars(templ: &ProjectTemplate) -> String {
+    let mut usages = find_usages(templ);
+    let mut out = String::new();
+
+    for name in var_names(templ) {
+        let declared = templ.variables.iter().find(|v| v.name == name);
+        let var_usages = usages.remove(&name).unwrap_or_default();
+
+        out.push_str(&name);
+        if declared.is_none() {
+            out.push_str(" (used but not declared)");
+        } else if var_usages.is_empty() {
+            out.push_str(" (declared but never used)");
+        }
+        out.push('\n');
+
+        if let Some(default) = declared.and_then(|v| v.default.as_ref()) {
+            out.push_str(&format!("    default: {}\n", default));
+        }
+
+        for usage in var_usages {
+            out.push_str(&match usage {
+                Usage::Directory(d) => format!("    directory {}\n", d),
+                Usage::Path(p) => format!("    path {}\n", p),
+                Usage::Content(p, line) => format!("    content {}:{}\n", p, line),
+                Usage::Default(v) => format!("    default of {}\n", v),
+                Usage::Hook(c) => format!("    hook {}\n", c),
+            });
+        }
+    }
+
+    return out;
+}
+
+/// Definitions file with an entry for every variable in the template.
+///
+/// Variables with a default are left commented out so the default is still used unless changed.
+fn skeleton_defs(templ: &ProjectTemplate) -> Result<String, VariablesError> {
+    let mut out = String::new();
+
+    for name in var_names(templ) {
+        let declared = templ.variables.iter().find(|v| v.name == name);
+
+        if let Some(desc) = declared.and_then(|v| v.description.as_ref()) {
+            out.push_str(&format!("# {}\n", desc));
+        }
+
+        let is_list = declared.is_some_and(|v| v.var_type == Some(VariableType::List));
+        let (value, commented) = match declared.and_then(|v| v.default.clone()) {
+            Some(default) => (default, true),
+            None if is_list => (Value::List(vec![]), false),
+            None => (Value::Scalar(String::new()), false),
+        };
+
+        let entry = match serde_yaml::to_string(&BTreeMap::from([(&name, value)])) {
+            Ok(e) => e,
+            Err(e) => return Err(VariablesError::SerializationError(e.to_string())),
+        };
+
+        for line in entry.lines() {
+            if commented {
+                out.push_str("# ");
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+
+    return Ok(out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe_metadata, describe_vars, find_usages, skeleton_defs, Usage};
+    use crate::{
+        render::{Definitions, Value},
+        template::{
+            Hooks, ProjectTemplate, TemplateFile, TemplateMetadata, Variable, VariableType,
+        },
+    };
+
+    fn template() -> ProjectTemplate {
+        return ProjectTemplate {
+            hooks: Hooks {
+                pre_gen: vec![],
+                post_gen: vec!["git init && git add {@ project_name @}".to_string()],
+            },
+            variables: vec![
+                Variable {
+                    description: Some("Name of the project".to_string()),
+                    ..Variable::from("project_name")
+                },
+                Variable {
+                    default: Some("{@ project_name @}_ns".into()),
+                    ..Variable::from("namespace")
+                },
+                Variable {
+                    var_type: Some(VariableType::List),
+                    ..Variable::from("modules")
+                },
+                Variable::from("unused"),
+            ],
+            directories: vec!["src/{@ namespace @}".to_string()],
+            files: vec![
+                TemplateFile {
+                    path: "CMakeLists.txt".to_string(),
+                    content: "project({@ project_name @})\n\n{@ for m in modules @}\nadd_subdirectory({@ m @})\n{@ end @}\n{@ version @}".to_string(),
+                    encoding: None,
+                    mode: None,
+                },
+                TemplateFile {
+                    path: "src/{@ for m in modules @}{@ m @}{@ end @}.cpp".to_string(),
+                    content: "namespace {@ namespace @}::{@ m @}".to_string(),
+                    encoding: None,
+                    mode: None,
+                },
+            ],
+            ..ProjectTemplate::default()
+        };
+    }
+
+    #[test]
+    fn usages() {
+        let usages = find_usages(&template());
+
+        assert!(
+            usages["project_name"]
+                == vec![
+                    Usage::Default("namespace".to_string()),
+                    Usage::Content("CMakeLists.txt".to_string(), 1),
+                    Usage::Hook("git init && git add {@ project_name @}".to_string()),
+                ]
+        );
+        assert!(
+            usages["namespace"]
+                == vec![
+                    Usage::Directory("src/{@ namespace @}".to_string()),
+                    Usage::Content(
+                        "src/{@ for m in modules @}{@ m @}{@ end @}.cpp".to_string(),
+                        1
+                    ),
+                ]
+        );
+        assert!(
+            usages["modules"]
+                == vec![
+                    Usage::Content("CMakeLists.txt".to_string(), 3),
+                    Usage::Path("src/{@ for m in modules @}{@ m @}{@ end @}.cpp".to_string()),
+                ]
+        );
+        assert!(!usages.contains_key("m"));
+        assert!(!usages.contains_key("unused"));
+    }
+
+    #[test]
+    fn describe() {
+        let description = describe_vars(&template());
+
+        assert!(description.contains("namespace\n    default: {@ project_name @}_ns\n"));
+        assert!(description.contains("unused (declared but never used)\n"));
+        assert!(description
+            .ends_with("version (used but not declared)\n    content CMakeLists.txt:6\n"));
+    }
+
+    #[test]
+    fn skeleton() {
+        let skeleton = skeleton_defs(&template()).expect("Error writing skeleton");
+
+        assert!(
+            skeleton
+                == "# Name of the project
+project_name: ''
+# namespace: '{@ project_name @}_ns'
+modules: []
+unused: ''
+version: ''
+"
+        );
+
+        // The skeleton is a valid defs file
+        let defs: Definitions = serde_yaml::from_str(&skeleton).expect("Error reading skeleton");
+        assert!(defs["modules"] == Value::List(vec![]));
+        assert!(!defs.contains_key("namespace"));
+    }
+
+    #[test]
+    fn metadata() {
+        let metadata = TemplateMetadata {
+            name: Some("cpp-lib".to_string()),
+            version: Some("1.2.0".to_string()),
+            description: Some("C++ library with CMake".to_string()),
+            authors: vec!["Ana".to_string(), "Ben".to_string()],
+            min_pgen_version: Some("0.1".to_string()),
+        };
+
+        assert!(
+            describe_metadata(&metadata)
+                == "cpp-lib 1.2.0\nC++ library with CMake\nAuthors: Ana, Ben\nNeeds pgen 0.1 or newer\n"
+        );
+        assert!(describe_metadata(&TemplateMetadata::default()).is_empty());
+    }
+}
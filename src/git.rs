@@ -0,0 +1,312 @@
+use crate::{library::xdg_dir, paths::escapes_root};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GitError {
+    #[error("Unable to find a cache directory for git templates, set XDG_CACHE_HOME or HOME")]
+    NoCacheDirError,
+
+    #[error("Git command failed")]
+    CommandError(String),
+
+    #[error("Unknown git reference")]
+    ReferenceError(String),
+
+    #[error("Path not found in git repository")]
+    SubpathError(String),
+}
+
+/// A template in a git repository, written as `url[//subpath][#ref]`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GitSource {
+    pub url: String,
+
+    /// Template file or directory inside the repository, the whole repository if not given
+    pub subpath: Option<String>,
+
+    /// Branch, tag or commit to check out, the repository's default branch if not given
+    pub reference: Option<String>,
+}
+
+/// A template source checked out into the cache.
+pub struct Checkout {
+    /// Template file or directory in the checkout
+    pub path: PathBuf,
+
+    /// Commit that was checked out
+    pub commit: String,
+}
+
+impl GitSource {
+    /// Parse `source` if it names a git repository, by a url scheme or a path ending in `.git`.
+    pub fn parse(source: &str) -> Option<GitSource> {
+        let (rest, reference) = match source.rsplit_once('#') {
+            Some((rest, r)) => (rest, Some(r.to_string())),
+            None => (source, None),
+        };
+
+        // The subpath follows a double slash after the scheme
+        let scheme_end = rest.find("://").map_or(0, |i| i + 3);
+        let (url, subpath) = match rest[scheme_end..].find("//") {
+            Some(i) => (
+                &rest[..scheme_end + i],
+                Some(rest[scheme_end + i + 2..].to_string()),
+            ),
+            None => (rest, None),
+        };
+
+        // Nothing starting with '-' reaches git, where it would be read as an option
+        if !is_git_url(url) || reference.as_deref().is_some_and(|r| r.starts_with('-')) {
+            return None;
+        }
+
+        return Some(GitSource {
+            url: url.to_string(),
+            subpath: subpath.filter(|s| !s.is_empty()),
+            reference: reference.filter(|r| !r.is_empty()),
+        });
+    }
+
+    /// Clone or update the repository in the user's cache directory and check out the reference.
+    pub fn checkout(&self) -> Result<Checkout, GitError> {
+        let Some(cache) = xdg_dir("XDG_CACHE_HOME", ".cache") else {
+            return Err(GitError::NoCacheDirError);
+        };
+
+        return self.checkout_in(&cache.join("pgen/git"));
+    }
+
+    /// Clone or update the repository in `cache` and check out the reference.
+    fn checkout_in(&self, cache: &Path) -> Result<Checkout, GitError> {
+        let repo = cache.join(cache_name(&self.url));
+
+        if repo.join(".git").exists() {
+            git(
+                &repo,
+                &[
+                    "fetch",
+                    "--quiet",
+                    "--force",
+                    "--tags",
+                    "origin",
+                    "+refs/heads/*:refs/remotes/origin/*",
+                ],
+            )?;
+            git(&repo, &["remote", "set-head", "origin", "--auto"])?;
+        } else {
+            if std::fs::create_dir_all(cache).is_err() {
+                return Err(GitError::CommandError(format!(
+                    "Unable to create {}",
+                    cache.display()
+                )));
+            }
+            git(
+                cache,
+                &[
+                    "clone",
+                    "--quiet",
+                    "--no-checkout",
+                    "--",
+                    &self.url,
+                    &cache_name(&self.url),
+                ],
+            )?;
+        }
+
+        let commit = self.resolve(&repo)?;
+        git(
+            &repo,
+            &["checkout", "--quiet", "--force", "--detach", &commit],
+        )?;
+
+        let path = match &self.subpath {
+            Some(sub) if escapes_root(Path::new(sub)) => {
+                return Err(GitError::SubpathError(sub.clone()))
+            }
+            Some(sub) => repo.join(sub),
+            None => repo,
+        };
+        if !path.exists() {
+            return Err(GitError::SubpathError(
+                self.subpath.clone().unwrap_or_default(),
+            ));
+        }
+
+        return Ok(Checkout { path, commit });
+    }
+
+    /// Commit the reference points at, branches are taken from the remote so they are up to date.
+    fn resolve(&self, repo: &Path) -> Result<String, GitError> {
+        let candidates = match &self.reference {
+            Some(r) => vec![format!("origin/{}", r), r.clone()],
+            None => vec!["origin/HEAD".to_string()],
+        };
+
+        for candidate in candidates {
+            let rev = format!("{}^{{commit}}", candidate);
+            if let Ok(commit) = git(repo, &["rev-parse", "--verify", "--quiet", &rev]) {
+                return Ok(commit);
+            }
+        }
+
+        return Err(GitError::ReferenceError(
+            self.reference.clone().unwrap_or("HEAD".to_string()),
+        ));
+    }
+}
+
+pub(crate) fn is_git_url(source: &str) -> bool {
+    if source.starts_with('-') {
+        return false;
+    }
+
+    return ["file://", "git://", "ssh://", "http://", "https://", "git@"]
+        .iter()
+        .any(|p| source.starts_with(p))
+        || source.trim_end_matches('/').ends_with(".git");
+}
+
+/// Directory name in the cache for the repository at `url`.
+fn cache_name(url: &str) -> String {
+    return url
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+}
+
+/// Run git in `dir`, returning what it printed.
+fn git(dir: &Path, args: &[&str]) -> Result<String, GitError> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output();
+
+    return match output {
+        Ok(o) if o.status.success() => Ok(String::from_utf8_lossy(&o.stdout).trim().to_string()),
+        Ok(o) => Err(GitError::CommandError(
+            String::from_utf8_lossy(&o.stderr).trim().to_string(),
+        )),
+        Err(e) => Err(GitError::CommandError(e.to_string())),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{git, GitError, GitSource};
+    use std::path::Path;
+
+    fn commit(repo: &Path, file: &str, content: &str) -> String {
+        std::fs::create_dir_all(repo.join(file).parent().unwrap()).expect("Error creating dir");
+        std::fs::write(repo.join(file), content).expect("Error writing file");
+        git(repo, &["add", "-A"]).expect("Error adding");
+        git(
+            repo,
+            &[
+                "-c",
+                "user.name=pgen",
+                "-c",
+                "user.email=pgen@example.com",
+                "commit",
+                "--quiet",
+                "-m",
+                content,
+            ],
+        )
+        .expect("Error committing");
+
+        return git(repo, &["rev-parse", "HEAD"]).expect("Error reading commit");
+    }
+
+    #[test]
+    fn parse() {
+        assert!(
+            GitSource::parse("file:///srv/git/templates.git//cpp/lib#v1.2")
+                == Some(GitSource {
+                    url: "file:///srv/git/templates.git".to_string(),
+                    subpath: Some("cpp/lib".to_string()),
+                    reference: Some("v1.2".to_string()),
+                })
+        );
+        assert!(
+            GitSource::parse("../templates.git#main")
+                == Some(GitSource {
+                    url: "../templates.git".to_string(),
+                    subpath: None,
+                    reference: Some("main".to_string()),
+                })
+        );
+        assert!(
+            GitSource::parse("git@example.com:team/templates.git//lib.yaml")
+                .is_some_and(|s| s.subpath.as_deref() == Some("lib.yaml"))
+        );
+        assert!(GitSource::parse("templates/lib.yaml").is_none());
+        assert!(GitSource::parse("cmake-lib").is_none());
+        assert!(GitSource::parse("--upload-pack=touch x.git").is_none());
+        assert!(GitSource::parse("https://example.com/t.git#--output=x").is_none());
+    }
+
+    #[test]
+    fn checkout_bare_repo() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let work = dir.path().join("work");
+        let bare = dir.path().join("templates.git");
+        let cache = dir.path().join("cache");
+
+        std::fs::create_dir(&work).expect("Error creating dir");
+        git(&work, &["init", "--quiet", "--initial-branch", "main"]).expect("Error creating repo");
+        let first = commit(&work, "cpp/lib/pgen.yaml", "first");
+        git(&work, &["tag", "v1"]).expect("Error tagging");
+        git(
+            dir.path(),
+            &["clone", "--quiet", "--bare", "work", "templates.git"],
+        )
+        .expect("Error cloning");
+
+        let url = format!("file://{}", bare.display());
+        let source = GitSource::parse(&format!("{}//cpp/lib", url)).expect("Not a git source");
+        let checkout = source.checkout_in(&cache).expect("Error checking out");
+        assert!(checkout.commit == first);
+        assert!(checkout.path.join("pgen.yaml").exists());
+
+        // New commits are fetched, tags and commits can be checked out
+        let second = commit(&work, "cpp/lib/pgen.yaml", "second");
+        git(&work, &["push", "--quiet", bare.to_str().unwrap(), "main"]).expect("Error pushing");
+
+        let checkout = source.checkout_in(&cache).expect("Error checking out");
+        assert!(checkout.commit == second);
+
+        let tagged = GitSource::parse(&format!("{}#v1", url)).expect("Not a git source");
+        assert!(
+            tagged
+                .checkout_in(&cache)
+                .expect("Error checking out")
+                .commit
+                == first
+        );
+
+        let by_commit = GitSource::parse(&format!("{}#{}", url, &first[..10])).unwrap();
+        assert!(
+            by_commit
+                .checkout_in(&cache)
+                .expect("Error checking out")
+                .commit
+                == first
+        );
+
+        let missing = GitSource::parse(&format!("{}#nope", url)).unwrap();
+        assert!(matches!(
+            missing.checkout_in(&cache),
+            Err(GitError::ReferenceError(_))
+        ));
+
+        let outside = GitSource::parse(&format!("{}//../..", url)).unwrap();
+        assert!(matches!(
+            outside.checkout_in(&cache),
+            Err(GitError::SubpathError(_))
+        ));
+    }
+}
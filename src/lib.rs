@@ -1,7 +1,17 @@
+#![allow(clippy::needless_return)]
+
 pub mod cmd;
+pub mod git;
+pub mod library;
+pub mod paths;
+pub mod prompt;
 pub mod render;
 pub mod template;
 
 // pub use cmd::fd::fd;
 pub use cmd::fd::fd;
-pub use cmd::gen::gen;
+pub use cmd::gen::{gen, ConflictPolicy, GenOptions, PlanFormat};
+pub use cmd::install::install;
+pub use cmd::list::list;
+pub use cmd::validate::validate;
+pub use cmd::vars::vars;
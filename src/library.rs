@@ -0,0 +1,255 @@
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LibraryError {
+    #[error("Unable to find a directory for the template library, set XDG_DATA_HOME or HOME")]
+    NoDataDirError,
+
+    #[error("Unable to read config file")]
+    ConfigReadError(PathBuf, String),
+
+    #[error("Unable to read template sources")]
+    SourcesReadError(PathBuf, String),
+
+    #[error("Unable to write template sources")]
+    SourcesWriteError(PathBuf),
+}
+
+/// Settings read from `pgen/config.yaml` in the user's config directory.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// More directories to look for templates in
+    #[serde(default)]
+    template_paths: Vec<String>,
+}
+
+/// Named templates found in the user's template directories.
+///
+/// Each template is a yaml file or a template directory directly inside one of the directories,
+/// named after the file without its extension or after the directory.
+pub struct Library {
+    /// Where templates are installed, searched first
+    pub install_dir: PathBuf,
+
+    /// Directories listed in the config file, searched in order
+    pub search_dirs: Vec<PathBuf>,
+
+    /// Where each installed template was installed from
+    sources_path: PathBuf,
+}
+
+impl Library {
+    /// Open the library under `$XDG_DATA_HOME/pgen`, with the directories listed in
+    /// `$XDG_CONFIG_HOME/pgen/config.yaml`.
+    pub fn open() -> Result<Library, LibraryError> {
+        let Some(data_dir) = xdg_dir("XDG_DATA_HOME", ".local/share") else {
+            return Err(LibraryError::NoDataDirError);
+        };
+        let config_path = xdg_dir("XDG_CONFIG_HOME", ".config").map(|d| d.join("pgen/config.yaml"));
+
+        return Library::with_dirs(&data_dir.join("pgen"), config_path.as_deref());
+    }
+
+    /// Open the library stored in `data_dir`, with the directories listed in the config file at
+    /// `config_path` if it exists.
+    pub fn with_dirs(data_dir: &Path, config_path: Option<&Path>) -> Result<Library, LibraryError> {
+        let config = match config_path {
+            Some(p) if p.exists() => read_config(p)?,
+            _ => Config::default(),
+        };
+
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let search_dirs = config
+            .template_paths
+            .iter()
+            .map(|p| match (p.strip_prefix("~/"), &home) {
+                (Some(rest), Some(h)) => h.join(rest),
+                _ => PathBuf::from(p),
+            })
+            .collect();
+
+        return Ok(Library {
+            install_dir: data_dir.join("templates"),
+            search_dirs,
+            sources_path: data_dir.join("sources.yaml"),
+        });
+    }
+
+    /// Every template in the library by name, a name found in an earlier directory hides later ones.
+    pub fn templates(&self) -> BTreeMap<String, PathBuf> {
+        let mut templates = BTreeMap::new();
+
+        for dir in std::iter::once(&self.install_dir).chain(&self.search_dirs) {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if let Some(name) = template_name(&path) {
+                    templates.entry(name).or_insert(path);
+                }
+            }
+        }
+
+        return templates;
+    }
+
+    /// Path of the template called `name`.
+    pub fn find(&self, name: &str) -> Option<PathBuf> {
+        return self.templates().remove(name);
+    }
+
+    /// Where each installed template was installed from, by name.
+    pub fn sources(&self) -> Result<BTreeMap<String, String>, LibraryError> {
+        let Ok(file) = File::open(&self.sources_path) else {
+            return Ok(BTreeMap::new());
+        };
+
+        return match serde_yaml::from_reader(file) {
+            Ok(s) => Ok(s),
+            Err(e) => Err(LibraryError::SourcesReadError(
+                self.sources_path.clone(),
+                e.to_string(),
+            )),
+        };
+    }
+
+    /// Remember that the template called `name` was installed from `source`.
+    pub fn record_source(&self, name: &str, source: &str) -> Result<(), LibraryError> {
+        let mut sources = self.sources()?;
+        sources.insert(name.to_string(), source.to_string());
+
+        let Ok(yaml) = serde_yaml::to_string(&sources) else {
+            return Err(LibraryError::SourcesWriteError(self.sources_path.clone()));
+        };
+        if std::fs::write(&self.sources_path, yaml).is_err() {
+            return Err(LibraryError::SourcesWriteError(self.sources_path.clone()));
+        }
+
+        return Ok(());
+    }
+}
+
+fn read_config(path: &Path) -> Result<Config, LibraryError> {
+    let Ok(file) = File::open(path) else {
+        return Err(LibraryError::ConfigReadError(
+            path.to_path_buf(),
+            "Unable to open file".to_string(),
+        ));
+    };
+
+    return match serde_yaml::from_reader(file) {
+        Ok(c) => Ok(c),
+        Err(e) => Err(LibraryError::ConfigReadError(
+            path.to_path_buf(),
+            e.to_string(),
+        )),
+    };
+}
+
+/// Directory named by the XDG environment variable `var`, or `fallback` in the home directory.
+pub(crate) fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    return match std::env::var_os(var) {
+        Some(d) if !d.is_empty() => Some(PathBuf::from(d)),
+        _ => std::env::var_os("HOME").map(|h| PathBuf::from(h).join(fallback)),
+    };
+}
+
+/// Name of the template at `path` if it is one, hidden files are never templates.
+fn template_name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    if name.starts_with('.') {
+        return None;
+    }
+
+    if path.is_dir() {
+        return Some(name.to_string());
+    }
+
+    return match name.rsplit_once('.') {
+        Some((stem, "yaml" | "yml")) => Some(stem.to_string()),
+        _ => None,
+    };
+}
+
+/// Path of the library template called `templ` when `templ` isn't a path to a template itself.
+///
+/// Anything else, including names the library doesn't have, is returned unchanged so the error
+/// points at what was asked for. Fails if the library has to be searched and can't be opened.
+pub fn resolve_template(templ: &Path) -> Result<PathBuf, LibraryError> {
+    if templ.exists() || templ.components().count() != 1 {
+        return Ok(templ.to_path_buf());
+    }
+
+    let found = Library::open()?.find(&templ.to_string_lossy());
+
+    return Ok(found.unwrap_or_else(|| templ.to_path_buf()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Library;
+    use std::path::PathBuf;
+
+    #[test]
+    fn templates() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let data = dir.path().join("data");
+        let extra = dir.path().join("extra");
+        let config = dir.path().join("config.yaml");
+
+        for d in ["data/templates/cmake-lib", "extra/cmake-app", "extra/.git"] {
+            std::fs::create_dir_all(dir.path().join(d)).expect("Error creating dir");
+        }
+        for f in [
+            "data/templates/cli.yaml",
+            "extra/cli.yml",
+            "extra/gui.yml",
+            "extra/notes.txt",
+        ] {
+            std::fs::write(dir.path().join(f), "").expect("Error writing file");
+        }
+        std::fs::write(
+            &config,
+            format!("template_paths: ['{}']\n", extra.display()),
+        )
+        .expect("Error writing config");
+
+        let lib = Library::with_dirs(&data, Some(&config)).expect("Error opening library");
+        let templates = lib.templates();
+
+        assert!(
+            templates.keys().collect::<Vec<&String>>()
+                == vec!["cli", "cmake-app", "cmake-lib", "gui"]
+        );
+        assert!(templates["cli"] == data.join("templates/cli.yaml"));
+        assert!(lib.find("cmake-app") == Some(extra.join("cmake-app")));
+        assert!(lib.find("notes").is_none());
+    }
+
+    #[test]
+    fn sources() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let lib = Library::with_dirs(dir.path(), None).expect("Error opening library");
+
+        assert!(lib.sources().expect("Error reading sources").is_empty());
+
+        lib.record_source("cli", "/home/ana/cli.yaml")
+            .expect("Error recording source");
+        lib.record_source("lib", "file:///srv/git/lib.git")
+            .expect("Error recording source");
+
+        let sources = lib.sources().expect("Error reading sources");
+        assert!(sources["cli"] == "/home/ana/cli.yaml");
+        assert!(sources["lib"] == "file:///srv/git/lib.git");
+        assert!(lib.install_dir == PathBuf::from(dir.path()).join("templates"));
+    }
+}
@@ -1,5 +1,7 @@
+#![allow(clippy::needless_return)]
+
 use clap::{Parser, Subcommand};
-use pgen::{fd, gen};
+use pgen::{fd, gen, install, list, validate, vars, ConflictPolicy, GenOptions, PlanFormat};
 use std::{error::Error, path::PathBuf};
 
 #[derive(Subcommand, Debug)]
@@ -9,13 +11,33 @@ enum Commands {
         /// Path where project will be generated
         root: PathBuf,
 
-        /// Path to template that will be used to generate project
+        /// Path to template file or directory that will be used to generate project
         #[arg(long)]
         template: PathBuf,
 
-        /// Path to file defining variables in template
+        /// Path to file defining variables in template, missing variables are prompted for
         #[arg(long)]
-        definitions: PathBuf,
+        definitions: Option<PathBuf>,
+
+        /// Generate into a root directory that already exists
+        #[arg(long, default_value_t = false)]
+        into_existing: bool,
+
+        /// What to do with files that already exist when generating into an existing root
+        #[arg(long, value_enum, default_value_t = ConflictPolicy::Fail, requires = "into_existing")]
+        on_conflict: ConflictPolicy,
+
+        /// Print the directories and files that would be generated without writing anything
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// Output format of a dry run
+        #[arg(long, value_enum, default_value_t = PlanFormat::Text, requires = "dry_run")]
+        format: PlanFormat,
+
+        /// Don't run the template's pre_gen and post_gen commands
+        #[arg(long, default_value_t = false)]
+        no_hooks: bool,
     },
 
     /// Generate template file from directory
@@ -30,9 +52,69 @@ enum Commands {
         /// Overwrite output if it already exists
         #[arg(long, default_value_t = false)]
         force: bool,
+
+        /// Glob of paths to leave out of the template, can be repeated
+        #[arg(long)]
+        exclude: Vec<String>,
+
+        /// Glob of files to include, only matching files are kept when given, can be repeated
+        #[arg(long)]
+        include: Vec<String>,
+
+        /// Value to replace with a variable, given as name=value, can be repeated
+        #[arg(long, value_parser = parse_var)]
+        var: Vec<(String, String)>,
+
+        /// Path to file defining values to replace with variables
+        #[arg(long)]
+        definitions: Option<PathBuf>,
+    },
+
+    /// List the variables of a template and where they are used
+    Vars {
+        /// Path to template file or directory to inspect
+        template: PathBuf,
+
+        /// Path where a definitions file to fill in will be written
+        #[arg(long)]
+        emit_defs: Option<PathBuf>,
+
+        /// Overwrite the definitions file if it already exists
+        #[arg(long, default_value_t = false, requires = "emit_defs")]
+        force: bool,
+    },
+
+    /// Check a template for problems, exits with an error if any are found
+    Validate {
+        /// Path to template file or directory to check
+        template: PathBuf,
+    },
+
+    /// List the templates in the template library
+    List,
+
+    /// Copy a template into the template library, installing again updates it
+    Install {
+        /// Path to template file or directory, or url of a git repository holding a template
+        source: String,
+
+        /// Name to install the template as, defaults to the name of its file or repository
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Replace a template already installed with the same name
+        #[arg(long, default_value_t = false)]
+        force: bool,
     },
 }
 
+fn parse_var(s: &str) -> Result<(String, String), String> {
+    return match s.split_once('=') {
+        Some((name, value)) => Ok((name.to_string(), value.to_string())),
+        None => Err(format!("Expected name=value, found '{}'", s)),
+    };
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
@@ -50,12 +132,52 @@ fn main() -> Result<(), Box<dyn Error>> {
             root,
             template,
             definitions,
-        }) => Ok(gen(root, template, definitions)?),
+            into_existing,
+            on_conflict,
+            dry_run,
+            format,
+            no_hooks,
+        }) => Ok(gen(
+            root,
+            template,
+            definitions.as_deref(),
+            &GenOptions {
+                into_existing: *into_existing,
+                on_conflict: *on_conflict,
+                dry_run: *dry_run,
+                format: *format,
+                no_hooks: *no_hooks,
+            },
+        )?),
         Some(Commands::Fd {
             directory,
             output,
             force,
-        }) => Ok(fd(directory, output, *force)?),
+            exclude,
+            include,
+            var,
+            definitions,
+        }) => Ok(fd(
+            directory,
+            output,
+            *force,
+            exclude,
+            include,
+            var,
+            definitions.as_deref(),
+        )?),
+        Some(Commands::Vars {
+            template,
+            emit_defs,
+            force,
+        }) => Ok(vars(template, emit_defs.as_deref(), *force)?),
+        Some(Commands::Validate { template }) => Ok(validate(template)?),
+        Some(Commands::List) => Ok(list()?),
+        Some(Commands::Install {
+            source,
+            name,
+            force,
+        }) => Ok(install(source, name.as_deref(), *force)?),
         None => Ok(()),
     }
 }
@@ -0,0 +1,298 @@
+use crate::template::{normalize_path, FileEncoding, FileMode, TemplateFile};
+use base64::Engine;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{overrides::OverrideBuilder, WalkBuilder};
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DirectoryError {
+    #[error("Unable to read content from file")]
+    FileReadError(PathBuf, String),
+
+    #[error("Unable to convert path to string")]
+    PathConversionError(PathBuf),
+
+    #[error("Unable to read path in directory")]
+    PathReadError(Option<PathBuf>),
+
+    #[error("Invalid include or exclude glob")]
+    InvalidGlobError(String),
+}
+
+/// Whether `path` is absolute or climbs out of the directory it is joined to, by its components alone.
+pub(crate) fn escapes_root(path: &Path) -> bool {
+    let mut depth = 0;
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return true,
+        }
+    }
+
+    return false;
+}
+
+/// Read every directory and file under `root` that isn't ignored, with paths relative to `root`.
+pub(crate) fn read_directory(
+    root: &Path,
+    exclude: &[String],
+    include: &[String],
+) -> Result<(Vec<String>, Vec<TemplateFile>), DirectoryError> {
+    // Recursively collect all paths in src that aren't ignored
+    let paths = get_paths_from_root(root, exclude, include)?;
+
+    // Read contents of paths that are files
+    let dirs = paths
+        .iter()
+        .filter(|p| p.is_dir())
+        .map(|p| {
+            let Ok(rel_path) = p.strip_prefix(root) else {
+                return Err(DirectoryError::PathConversionError(p.to_path_buf()));
+            };
+
+            let Some(str_path) = rel_path.to_str() else {
+                return Err(DirectoryError::PathConversionError(p.to_path_buf()));
+            };
+
+            return Ok(normalize_path(str_path));
+        })
+        .collect::<Result<Vec<String>, DirectoryError>>()?;
+
+    let files = paths
+        .into_iter()
+        .filter(|p| p.is_file())
+        .map(|p| {
+            let Ok(rel_path) = p.strip_prefix(root) else {
+                return Err(DirectoryError::PathConversionError(p.to_path_buf()));
+            };
+
+            let Some(str_path) = rel_path.to_str() else {
+                return Err(DirectoryError::PathConversionError(p.to_path_buf()));
+            };
+
+            let bytes = match std::fs::read(&p) {
+                Ok(b) => b,
+                Err(e) => return Err(DirectoryError::FileReadError(p, e.to_string())),
+            };
+
+            let (content, encoding) = encode_content(bytes);
+
+            return Ok(TemplateFile {
+                path: normalize_path(str_path),
+                content,
+                encoding,
+                mode: get_mode(&p),
+            });
+        })
+        .collect::<Result<Vec<TemplateFile>, DirectoryError>>()?;
+
+    return Ok((dirs, files));
+}
+
+/// Mode of executable files, other files are generated with default permissions.
+#[cfg(unix)]
+fn get_mode(path: &Path) -> Option<FileMode> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path).ok()?.permissions().mode() & 0o7777;
+    if mode & 0o111 == 0 {
+        return None;
+    }
+
+    return Some(FileMode(mode));
+}
+
+#[cfg(not(unix))]
+fn get_mode(_path: &Path) -> Option<FileMode> {
+    return None;
+}
+
+/// Text files are kept as is, anything else is base64 encoded so it is copied verbatim.
+fn encode_content(bytes: Vec<u8>) -> (String, Option<FileEncoding>) {
+    if !bytes.contains(&0) {
+        if let Ok(text) = String::from_utf8(bytes.clone()) {
+            return (text.replace("\r\n", "\n"), None);
+        }
+    }
+
+    return (
+        base64::engine::general_purpose::STANDARD.encode(bytes),
+        Some(FileEncoding::Base64),
+    );
+}
+
+fn get_paths_from_root(
+    root: &Path,
+    exclude: &[String],
+    include: &[String],
+) -> Result<Vec<PathBuf>, DirectoryError> {
+    let mut overrides = OverrideBuilder::new(root);
+    let globs = ["!.git/".to_string()]
+        .into_iter()
+        .chain(exclude.iter().map(|g| format!("!{}", g)));
+    for glob in globs {
+        if let Err(e) = overrides.add(&glob) {
+            return Err(DirectoryError::InvalidGlobError(e.to_string()));
+        }
+    }
+
+    let overrides = match overrides.build() {
+        Ok(o) => o,
+        Err(e) => return Err(DirectoryError::InvalidGlobError(e.to_string())),
+    };
+
+    // Includes only narrow what the walk finds, so they never bring back ignored files
+    let include = include_set(include)?;
+
+    return WalkBuilder::new(root)
+        .standard_filters(false)
+        .git_ignore(true)
+        .require_git(false)
+        .add_custom_ignore_filename(".pgenignore")
+        .overrides(overrides)
+        .sort_by_file_path(|a, b| a.cmp(b))
+        .build()
+        .map(|entry| {
+            return match entry {
+                Ok(e) => Ok(e.path().to_path_buf()),
+                Err(e) => Err(DirectoryError::PathReadError(error_path(&e))),
+            };
+        })
+        .filter(|path| {
+            return match (path, &include) {
+                (Ok(p), Some(set)) if !p.is_dir() => is_included(set, root, p),
+                _ => true,
+            };
+        })
+        .collect();
+}
+
+/// Include globs match like gitignore lines, a glob without a `/` matches a name at any depth.
+fn include_set(include: &[String]) -> Result<Option<GlobSet>, DirectoryError> {
+    if include.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for glob in include {
+        let trimmed = glob.trim_start_matches('/').trim_end_matches('/');
+        let pattern = if glob.trim_end_matches('/').contains('/') {
+            trimmed.to_string()
+        } else {
+            format!("**/{}", trimmed)
+        };
+
+        match GlobBuilder::new(&pattern).literal_separator(true).build() {
+            Ok(g) => builder.add(g),
+            Err(e) => return Err(DirectoryError::InvalidGlobError(e.to_string())),
+        };
+    }
+
+    return match builder.build() {
+        Ok(s) => Ok(Some(s)),
+        Err(e) => Err(DirectoryError::InvalidGlobError(e.to_string())),
+    };
+}
+
+/// A file is included when it or a directory it is in matches.
+fn is_included(set: &GlobSet, root: &Path, path: &Path) -> bool {
+    let Ok(rel_path) = path.strip_prefix(root) else {
+        return false;
+    };
+
+    return rel_path
+        .ancestors()
+        .filter(|p| !p.as_os_str().is_empty())
+        .any(|p| set.is_match(p));
+}
+
+fn error_path(err: &ignore::Error) -> Option<PathBuf> {
+    return match err {
+        ignore::Error::WithPath { path, .. } => Some(path.to_path_buf()),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        }
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_content, escapes_root, get_paths_from_root};
+    use crate::template::FileEncoding;
+    use std::{collections::HashSet, path::Path};
+
+    #[test]
+    fn test_encode_content() {
+        assert!(encode_content(b"{@ a @}\r\n".to_vec()) == ("{@ a @}\n".to_string(), None));
+        assert!(
+            encode_content(vec![0x89, b'P', b'N', b'G', 0])
+                == ("iVBORwA=".to_string(), Some(FileEncoding::Base64))
+        );
+    }
+
+    #[test]
+    fn test_get_paths_from_root_ignores() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path();
+        for p in [".git", "build", "src", "docs"] {
+            std::fs::create_dir(root.join(p)).unwrap();
+        }
+        for p in [
+            ".git/HEAD",
+            ".gitignore",
+            ".pgenignore",
+            "build/main.o",
+            "src/main.cpp",
+            "src/main.cpp.orig",
+            "docs/notes.txt",
+        ] {
+            std::fs::write(root.join(p), "").unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "build/\n").unwrap();
+        std::fs::write(root.join(".pgenignore"), "*.orig\n").unwrap();
+
+        let rel = |paths: Vec<std::path::PathBuf>| {
+            paths
+                .iter()
+                .map(|p| p.strip_prefix(root).unwrap().to_str().unwrap().to_string())
+                .collect::<HashSet<String>>()
+        };
+
+        let paths = rel(get_paths_from_root(root, &["docs".to_string()], &[]).unwrap());
+        assert!(
+            paths
+                == HashSet::from([
+                    "".to_string(),
+                    ".gitignore".to_string(),
+                    ".pgenignore".to_string(),
+                    "src".to_string(),
+                    "src/main.cpp".to_string(),
+                ])
+        );
+
+        let paths = rel(get_paths_from_root(root, &[], &["*.cpp".to_string()]).unwrap());
+        assert!(paths.contains("src/main.cpp"));
+        assert!(!paths.contains(".gitignore"));
+        assert!(!paths.contains("docs/notes.txt"));
+
+        // Includes never bring back ignored files
+        let include = ["*.o".to_string(), "*.orig".to_string(), "docs".to_string()];
+        let paths = rel(get_paths_from_root(root, &[], &include).unwrap());
+        assert!(!paths.contains("build/main.o"));
+        assert!(!paths.contains("src/main.cpp.orig"));
+        assert!(!paths.contains(".git/HEAD"));
+        assert!(paths.contains("docs/notes.txt"));
+    }
+
+    #[test]
+    fn test_escapes_root() {
+        assert!(!escapes_root(Path::new("src/../include")));
+        assert!(!escapes_root(Path::new("./src")));
+        assert!(escapes_root(Path::new("src/../../out")));
+        assert!(escapes_root(Path::new("/etc")));
+    }
+}
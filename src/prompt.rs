@@ -0,0 +1,208 @@
+use crate::{
+    render::{default_order, render_default, Definitions, Value},
+    template::{Variable, VariableType},
+};
+use std::io::{BufRead, Write};
+
+/// Ask for a value for every variable in `vars` that is not already in `defs`.
+///
+/// Prompts are written to `output` and answers are read line by line from `input`. An empty answer
+/// takes the variable's default if it has one, so variables a default refers to are asked for
+/// first, and an answer breaking the variable's rules is asked for again.
+pub fn prompt_for_missing(
+    vars: &[Variable],
+    defs: &mut Definitions,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    for var in default_order(vars) {
+        if defs.contains_key(&var.name) {
+            continue;
+        }
+
+        let default = match render_default(var, defs) {
+            Ok(d) => d,
+            Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        };
+
+        // Ask again until the answer follows the variable's rules
+        let value = loop {
+            let value = prompt(var, default.as_ref(), input, output)?;
+            match var.validate(&value) {
+                Ok(()) => break value,
+                Err(rule) => writeln!(output, "{} {}", var.name, rule)?,
+            }
+        };
+        defs.insert(var.name.clone(), value);
+    }
+
+    return Ok(());
+}
+
+/// Ask a yes or no question, anything other than yes is taken as no.
+pub fn confirm(
+    question: &str,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> std::io::Result<bool> {
+    write!(output, "{} [y/N]: ", question)?;
+    output.flush()?;
+
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+
+    return Ok(matches!(line.trim().to_lowercase().as_str(), "y" | "yes"));
+}
+
+fn prompt(
+    var: &Variable,
+    default: Option<&Value>,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> std::io::Result<Value> {
+    write!(output, "{}", var.name)?;
+    if let Some(desc) = &var.description {
+        write!(output, " ({})", desc)?;
+    }
+    if let Some(d) = default {
+        write!(output, " [{}]", d)?;
+    }
+    write!(output, ": ")?;
+    output.flush()?;
+
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("No value supplied for {}", var.name),
+        ));
+    }
+
+    let answer = line.trim_end_matches(['\r', '\n']);
+    if answer.is_empty() {
+        if let Some(d) = default {
+            return Ok(d.clone());
+        }
+    }
+
+    return Ok(match var.var_type {
+        Some(VariableType::List) => Value::List(
+            answer
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+        ),
+        _ => Value::Scalar(answer.to_string()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{confirm, prompt_for_missing};
+    use crate::{
+        render::Value,
+        template::{Variable, VariableType},
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn prompts_only_missing() {
+        let vars = vec![Variable::from("namespace"), Variable::from("project_name")];
+        let mut defs = HashMap::from([("namespace".to_string(), Value::from("passion"))]);
+        let mut input = "fruit\n".as_bytes();
+        let mut output = Vec::new();
+
+        prompt_for_missing(&vars, &mut defs, &mut input, &mut output).expect("Error prompting");
+
+        assert!(defs["namespace"] == Value::from("passion"));
+        assert!(defs["project_name"] == Value::from("fruit"));
+        assert!(String::from_utf8(output).unwrap() == "project_name: ");
+    }
+
+    #[test]
+    fn empty_answer_takes_default() {
+        let vars = vec![
+            Variable::from("project_name"),
+            Variable {
+                description: Some("Top level namespace".to_string()),
+                default: Some("{@ project_name @}_ns".into()),
+                ..Variable::from("namespace")
+            },
+            Variable {
+                var_type: Some(VariableType::List),
+                ..Variable::from("modules")
+            },
+        ];
+        let mut defs = HashMap::new();
+        let mut input = "fruit\n\ncore, io\n".as_bytes();
+        let mut output = Vec::new();
+
+        prompt_for_missing(&vars, &mut defs, &mut input, &mut output).expect("Error prompting");
+
+        assert!(defs["namespace"] == Value::from("fruit_ns"));
+        assert!(defs["modules"] == Value::List(vec!["core".to_string(), "io".to_string()]));
+        assert!(
+            String::from_utf8(output).unwrap()
+                == "project_name: namespace (Top level namespace) [fruit_ns]: modules: "
+        );
+    }
+
+    #[test]
+    fn referenced_variables_asked_first() {
+        let vars = vec![
+            Variable {
+                default: Some("{@ project_name @}_ns".into()),
+                ..Variable::from("namespace")
+            },
+            Variable::from("project_name"),
+        ];
+        let mut defs = HashMap::new();
+        let mut input = "fruit\n\n".as_bytes();
+        let mut output = Vec::new();
+
+        prompt_for_missing(&vars, &mut defs, &mut input, &mut output).expect("Error prompting");
+
+        assert!(defs["namespace"] == Value::from("fruit_ns"));
+        assert!(String::from_utf8(output).unwrap() == "project_name: namespace [fruit_ns]: ");
+    }
+
+    #[test]
+    fn invalid_answer_asked_again() {
+        let vars = vec![Variable {
+            var_type: Some(VariableType::Bool),
+            ..Variable::from("use_tests")
+        }];
+        let mut defs = HashMap::new();
+        let mut input = "maybe\nyes\n".as_bytes();
+        let mut output = Vec::new();
+
+        prompt_for_missing(&vars, &mut defs, &mut input, &mut output).expect("Error prompting");
+
+        assert!(defs["use_tests"] == Value::from("yes"));
+        assert!(
+            String::from_utf8(output).unwrap()
+                == "use_tests: use_tests must be a boolean\nuse_tests: "
+        );
+    }
+
+    #[test]
+    fn eof_is_error() {
+        let vars = vec![Variable::from("project_name")];
+        let mut defs = HashMap::new();
+        let mut input = "".as_bytes();
+        let mut output = Vec::new();
+
+        assert!(prompt_for_missing(&vars, &mut defs, &mut input, &mut output).is_err());
+    }
+
+    #[test]
+    fn confirm_answers() {
+        let mut output = Vec::new();
+
+        assert!(confirm("Overwrite?", &mut "y\n".as_bytes(), &mut output).unwrap());
+        assert!(confirm("Overwrite?", &mut "Yes\n".as_bytes(), &mut output).unwrap());
+        assert!(!confirm("Overwrite?", &mut "\n".as_bytes(), &mut output).unwrap());
+        assert!(!confirm("Overwrite?", &mut "".as_bytes(), &mut output).unwrap());
+    }
+}
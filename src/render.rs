@@ -1,66 +1,830 @@
-use crate::template::{ProjectTemplate, RenderedTemplate};
+mod filter;
+
+use crate::template::{
+    normalize_path, FileEncoding, ProjectTemplate, RenderedFile, RenderedTemplate, Variable,
+};
+use base64::Engine;
+use filter::{parse_filters, Filter};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, path::PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error("Block tag without matching opening block")]
+    UnexpectedTag(String),
+
+    #[error("Block opened but never closed")]
+    UnclosedBlock(String),
+
+    #[error("Unknown filter or wrong number of filter arguments")]
+    InvalidFilter(String),
+
+    #[error("Unable to decode binary file content")]
+    InvalidBinaryContent(String),
+
+    #[error("Variable's default refers to a variable with no value")]
+    UnresolvedDefault(String, String),
+}
+
+/// Value of a variable definition, either a single value or a list of values to loop over.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged, try_from = "serde_yaml::Value")]
+pub enum Value {
+    Scalar(String),
+    List(Vec<String>),
+}
+
+pub type Definitions = HashMap<String, Value>;
+
+fn scalar_to_string(value: serde_yaml::Value) -> Result<String, String> {
+    return match value {
+        serde_yaml::Value::Null => Ok(String::new()),
+        serde_yaml::Value::Bool(b) => Ok(b.to_string()),
+        serde_yaml::Value::Number(n) => Ok(n.to_string()),
+        serde_yaml::Value::String(s) => Ok(s),
+        _ => Err("Expected a scalar value".to_string()),
+    };
+}
 
-fn sub(mut str: String, defs: &HashMap<String, String>) -> String {
-    for (id, repl) in defs {
-        let regex_str = format!(r"\{{@\s*(?P<var>{})\s*@\}}", id);
-        let replace = repl;
-        let re = Regex::new(&regex_str).expect("Error compiling regex");
-        str = re.replace_all(&str, replace).to_string();
+impl TryFrom<serde_yaml::Value> for Value {
+    type Error = String;
+
+    fn try_from(value: serde_yaml::Value) -> Result<Self, Self::Error> {
+        return match value {
+            serde_yaml::Value::Sequence(seq) => Ok(Value::List(
+                seq.into_iter()
+                    .map(scalar_to_string)
+                    .collect::<Result<Vec<String>, String>>()?,
+            )),
+            v => Ok(Value::Scalar(scalar_to_string(v)?)),
+        };
     }
+}
 
-    return str;
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        return Value::Scalar(value.to_string());
+    }
 }
 
-pub fn render_template(templ: ProjectTemplate, defs: &HashMap<String, String>) -> RenderedTemplate {
-    return RenderedTemplate {
-        directories: templ
-            .directories
-            .into_iter()
-            .map(|s| PathBuf::from(sub(s, defs)))
-            .collect(),
-        files: templ
-            .files
-            .into_iter()
-            .map(|f| (PathBuf::from(sub(f.path, defs)), sub(f.content, defs)))
-            .collect(),
+impl Value {
+    fn items(&self) -> Vec<&str> {
+        return match self {
+            Value::Scalar(s) => vec![s.as_str()],
+            Value::List(l) => l.iter().map(|s| s.as_str()).collect(),
+        };
+    }
+
+    fn is_truthy(&self) -> bool {
+        return match self {
+            Value::Scalar(s) => !matches!(
+                s.trim().to_lowercase().as_str(),
+                "" | "false" | "no" | "off" | "0"
+            ),
+            Value::List(l) => !l.is_empty(),
+        };
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", self.items().join(", "));
+    }
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Tag { raw: &'a str, inner: &'a str },
+}
+
+enum Node {
+    Text(String),
+    Var {
+        name: String,
+        filters: Vec<Filter>,
+        raw: String,
+    },
+    If {
+        var: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        item: String,
+        list: String,
+        body: Vec<Node>,
+    },
+}
+
+fn is_block_tag(inner: &str) -> bool {
+    return inner == "else"
+        || inner == "end"
+        || inner.starts_with("if ")
+        || inner.starts_with("for ");
+}
+
+fn tokenize(src: &str) -> Vec<Token<'_>> {
+    let re = Regex::new(r"\{@(?P<inner>.*?)@\}").expect("Error compiling regex");
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    for c in re.captures_iter(src) {
+        let m = c.get(0).expect("Capture without match");
+        let inner = c
+            .name("inner")
+            .expect("Capture without inner")
+            .as_str()
+            .trim();
+        let (mut start, mut end) = (m.start(), m.end());
+
+        // A block tag alone on its line takes the whole line with it
+        if is_block_tag(inner) {
+            let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = src[end..].find('\n').map_or(src.len(), |i| end + i + 1);
+            if line_start >= pos
+                && src[line_start..start].trim().is_empty()
+                && src[end..line_end].trim().is_empty()
+            {
+                start = line_start;
+                end = line_end;
+            }
+        }
+
+        if start > pos {
+            tokens.push(Token::Text(&src[pos..start]));
+        }
+        tokens.push(Token::Tag {
+            raw: m.as_str(),
+            inner,
+        });
+        pos = end;
+    }
+
+    if pos < src.len() {
+        tokens.push(Token::Text(&src[pos..]));
+    }
+
+    return tokens;
+}
+
+/// Parse tokens into nodes until a closing tag is found, returning the nodes and the closing tag.
+fn parse_block<'a>(
+    tokens: &mut impl Iterator<Item = Token<'a>>,
+) -> Result<(Vec<Node>, Option<&'a str>), RenderError> {
+    let var_re = Regex::new(r"^(?P<var>\w+)(?P<filters>\s*\|.*)?$").expect("Error compiling regex");
+    let if_re = Regex::new(r"^if\s+(?P<var>\w+)$").expect("Error compiling regex");
+    let for_re =
+        Regex::new(r"^for\s+(?P<item>\w+)\s+in\s+(?P<list>\w+)$").expect("Error compiling regex");
+    let mut nodes = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        let (raw, inner) = match token {
+            Token::Text(t) => {
+                nodes.push(Node::Text(t.to_string()));
+                continue;
+            }
+            Token::Tag { raw, inner } => (raw, inner),
+        };
+
+        if inner == "else" || inner == "end" {
+            return Ok((nodes, Some(inner)));
+        }
+
+        if let Some(c) = if_re.captures(inner) {
+            let (then, close) = parse_block(tokens)?;
+            let otherwise = match close {
+                Some("else") => match parse_block(tokens)? {
+                    (otherwise, Some("end")) => otherwise,
+                    (_, Some(tag)) => return Err(RenderError::UnexpectedTag(tag.to_string())),
+                    (_, None) => return Err(RenderError::UnclosedBlock(raw.to_string())),
+                },
+                Some(_) => Vec::new(),
+                None => return Err(RenderError::UnclosedBlock(raw.to_string())),
+            };
+
+            nodes.push(Node::If {
+                var: c["var"].to_string(),
+                then,
+                otherwise,
+            });
+        } else if let Some(c) = for_re.captures(inner) {
+            let body = match parse_block(tokens)? {
+                (body, Some("end")) => body,
+                (_, Some(tag)) => return Err(RenderError::UnexpectedTag(tag.to_string())),
+                (_, None) => return Err(RenderError::UnclosedBlock(raw.to_string())),
+            };
+
+            nodes.push(Node::For {
+                item: c["item"].to_string(),
+                list: c["list"].to_string(),
+                body,
+            });
+        } else if let Some(c) = var_re.captures(inner) {
+            nodes.push(Node::Var {
+                name: c["var"].to_string(),
+                filters: parse_filters(c.name("filters").map_or("", |f| f.as_str()))?,
+                raw: raw.to_string(),
+            });
+        } else {
+            nodes.push(Node::Text(raw.to_string()));
+        }
+    }
+
+    return Ok((nodes, None));
+}
+
+fn parse(src: &str) -> Result<Vec<Node>, RenderError> {
+    return match parse_block(&mut tokenize(src).into_iter())? {
+        (nodes, None) => Ok(nodes),
+        (_, Some(tag)) => Err(RenderError::UnexpectedTag(tag.to_string())),
+    };
+}
+
+fn is_truthy(value: Option<&Value>) -> bool {
+    return value.is_some_and(|v| v.is_truthy());
+}
+
+/// Definitions with `item` bound to `value`, as seen inside a loop body.
+fn bind(defs: &Definitions, item: &str, value: &str) -> Definitions {
+    let mut inner = defs.clone();
+    inner.insert(item.to_string(), Value::from(value));
+
+    return inner;
+}
+
+fn eval(nodes: &[Node], defs: &Definitions, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(t) => out.push_str(t),
+            Node::Var { name, filters, raw } => match defs.get(name) {
+                Some(v) => out.push_str(
+                    &v.items()
+                        .into_iter()
+                        .map(|i| filters.iter().fold(i.to_string(), |acc, f| f.apply(&acc)))
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                ),
+                None => out.push_str(raw),
+            },
+            Node::If {
+                var,
+                then,
+                otherwise,
+            } => {
+                if is_truthy(defs.get(var)) {
+                    eval(then, defs, out);
+                } else {
+                    eval(otherwise, defs, out);
+                }
+            }
+            Node::For { item, list, body } => {
+                let Some(values) = defs.get(list) else {
+                    continue;
+                };
+
+                for value in values.items() {
+                    eval(body, &bind(defs, item, value), out);
+                }
+            }
+        }
+    }
+}
+
+/// Evaluate nodes as a path, producing one path per loop iteration.
+///
+/// Each path is paired with the definitions in scope at the end of it, so loop variables bound in
+/// a file path can be used in the file's content.
+fn expand(nodes: &[Node], states: Vec<(String, Definitions)>) -> Vec<(String, Definitions)> {
+    let mut states = states;
+
+    for node in nodes {
+        states = match node {
+            Node::For { item, list, body } => states
+                .into_iter()
+                .flat_map(|(path, defs)| {
+                    let values = defs.get(list).map(|v| v.items()).unwrap_or_default();
+                    return values
+                        .into_iter()
+                        .flat_map(|value| {
+                            expand(body, vec![(path.clone(), bind(&defs, item, value))])
+                        })
+                        .collect::<Vec<(String, Definitions)>>();
+                })
+                .collect(),
+            Node::If {
+                var,
+                then,
+                otherwise,
+            } => states
+                .into_iter()
+                .flat_map(|(path, defs)| {
+                    let branch = if is_truthy(defs.get(var)) {
+                        then
+                    } else {
+                        otherwise
+                    };
+                    return expand(branch, vec![(path, defs)]);
+                })
+                .collect(),
+            _ => states
+                .into_iter()
+                .map(|(mut path, defs)| {
+                    eval(std::slice::from_ref(node), &defs, &mut path);
+                    return (path, defs);
+                })
+                .collect(),
+        };
+    }
+
+    return states;
+}
+
+/// Check that the tags in `src` form complete blocks and use known filters, without rendering it.
+pub fn check_syntax(src: &str) -> Result<(), RenderError> {
+    return parse(src).map(|_| ());
+}
+
+/// Render a single string, such as a file's content, with `defs`.
+pub fn render_string(str: String, defs: &Definitions) -> Result<String, RenderError> {
+    let nodes = parse(&str)?;
+    let mut out = String::with_capacity(str.len());
+    eval(&nodes, defs, &mut out);
+
+    return Ok(out);
+}
+
+fn render_path(str: String, defs: &Definitions) -> Result<Vec<(String, Definitions)>, RenderError> {
+    return Ok(expand(
+        &parse(&normalize_path(&str))?,
+        vec![(String::new(), defs.clone())],
+    ));
+}
+
+/// Variables referenced by `nodes`, leaving out loop items bound within them.
+fn referenced_vars<'a>(nodes: &'a [Node], bound: &mut Vec<&'a str>, refs: &mut Vec<String>) {
+    for node in nodes {
+        let mut reference = |name: &String| {
+            if !bound.contains(&name.as_str()) && !refs.contains(name) {
+                refs.push(name.clone());
+            }
+        };
+
+        match node {
+            Node::Text(_) => {}
+            Node::Var { name, .. } => reference(name),
+            Node::If {
+                var,
+                then,
+                otherwise,
+            } => {
+                reference(var);
+                referenced_vars(then, bound, refs);
+                referenced_vars(otherwise, bound, refs);
+            }
+            Node::For { item, list, body } => {
+                reference(list);
+                bound.push(item);
+                referenced_vars(body, bound, refs);
+                bound.pop();
+            }
+        }
+    }
+}
+
+/// Variables a variable's default refers to.
+fn default_refs(var: &Variable) -> Result<Vec<String>, RenderError> {
+    let mut refs = Vec::new();
+    for item in var.default.iter().flat_map(|d| d.items()) {
+        referenced_vars(&parse(item)?, &mut Vec::new(), &mut refs);
+    }
+
+    return Ok(refs);
+}
+
+/// Variables in the order their defaults can be rendered, each after the variables its default
+/// refers to and otherwise in the order they are declared.
+pub fn default_order(vars: &[Variable]) -> Vec<&Variable> {
+    fn visit<'a>(
+        var: &'a Variable,
+        vars: &'a [Variable],
+        visiting: &mut Vec<&'a str>,
+        ordered: &mut Vec<&'a Variable>,
+    ) {
+        if visiting.contains(&var.name.as_str()) {
+            return;
+        }
+        visiting.push(&var.name);
+
+        // A default that doesn't parse is reported when it is rendered
+        for name in default_refs(var).unwrap_or_default() {
+            if let Some(dep) = vars.iter().find(|v| v.name == name) {
+                visit(dep, vars, visiting, ordered);
+            }
+        }
+        ordered.push(var);
+    }
+
+    let mut visiting = Vec::new();
+    let mut ordered = Vec::new();
+    for var in vars {
+        visit(var, vars, &mut visiting, &mut ordered);
+    }
+
+    return ordered;
+}
+
+/// Render a variable's default with the definitions resolved so far.
+///
+/// Every variable the default refers to must already have a value.
+pub fn render_default(var: &Variable, defs: &Definitions) -> Result<Option<Value>, RenderError> {
+    if let Some(name) = default_refs(var)?
+        .into_iter()
+        .find(|r| !defs.contains_key(r))
+    {
+        return Err(RenderError::UnresolvedDefault(var.name.clone(), name));
+    }
+
+    return match &var.default {
+        Some(Value::Scalar(s)) => Ok(Some(Value::Scalar(render_string(s.clone(), defs)?))),
+        Some(Value::List(l)) => Ok(Some(Value::List(
+            l.iter()
+                .map(|s| render_string(s.clone(), defs))
+                .collect::<Result<Vec<String>, RenderError>>()?,
+        ))),
+        None => Ok(None),
     };
 }
 
+/// Fill in variables missing from `defs` with their defaults, in the order given by
+/// [`default_order`].
+///
+/// Defaults referring to a declared variable that has no value yet are left for prompting.
+pub fn apply_defaults(vars: &[Variable], defs: &mut Definitions) -> Result<(), RenderError> {
+    for var in default_order(vars) {
+        if defs.contains_key(&var.name) {
+            continue;
+        }
+
+        let waiting = default_refs(var)?
+            .iter()
+            .any(|r| !defs.contains_key(r) && vars.iter().any(|v| &v.name == r));
+        if waiting {
+            continue;
+        }
+
+        if let Some(default) = render_default(var, defs)? {
+            defs.insert(var.name.clone(), default);
+        }
+    }
+
+    return Ok(());
+}
+
+pub fn render_template(
+    templ: ProjectTemplate,
+    defs: &Definitions,
+) -> Result<RenderedTemplate, RenderError> {
+    // Paths left empty by a false condition are skipped
+    let mut directories = Vec::new();
+    for dir in templ.directories {
+        directories.extend(
+            render_path(dir, defs)?
+                .into_iter()
+                .filter(|(p, _)| !p.is_empty())
+                .map(|(p, _)| PathBuf::from(p)),
+        );
+    }
+
+    let mut files = HashMap::new();
+    for file in templ.files {
+        // Binary content is copied as is
+        if let Some(FileEncoding::Base64) = file.encoding {
+            let Ok(content) = base64::engine::general_purpose::STANDARD.decode(&file.content)
+            else {
+                return Err(RenderError::InvalidBinaryContent(file.path));
+            };
+
+            for (path, _) in render_path(file.path, defs)? {
+                if path.is_empty() {
+                    continue;
+                }
+                files.insert(
+                    PathBuf::from(path),
+                    RenderedFile {
+                        content: content.clone(),
+                        mode: file.mode,
+                    },
+                );
+            }
+            continue;
+        }
+
+        let content = parse(&file.content)?;
+        for (path, file_defs) in render_path(file.path, defs)? {
+            if path.is_empty() {
+                continue;
+            }
+            let mut out = String::with_capacity(file.content.len());
+            eval(&content, &file_defs, &mut out);
+            files.insert(
+                PathBuf::from(path),
+                RenderedFile {
+                    content: out.into_bytes(),
+                    mode: file.mode,
+                },
+            );
+        }
+    }
+
+    return Ok(RenderedTemplate { directories, files });
+}
+
 #[cfg(test)]
 mod tests {
-    use super::render_template;
-    use crate::template::{ProjectTemplate, TemplateFile};
+    use super::{
+        apply_defaults, default_order, render_default, render_string, render_template, Definitions,
+        RenderError, Value,
+    };
+    use crate::template::{FileEncoding, ProjectTemplate, TemplateFile, Variable};
     use std::{collections::HashMap, path::PathBuf};
 
     #[test]
     fn render_test() {
         let pt = ProjectTemplate {
-            variables: vec!["namespace".to_string(), "project_name".to_string()],
+            variables: vec![Variable::from("namespace"), Variable::from("project_name")],
             directories: vec!["docs".to_string(), "include".to_string(), "src".to_string()],
             files: vec![TemplateFile {
                 path: r"include\{@ namespace @}\{@project_name@}.hpp".to_string(),
                 content: "{@ namespace@}{@project_name @}".to_string(),
+                encoding: None,
+                mode: None,
             }],
+            ..ProjectTemplate::default()
         };
 
-        let defs: HashMap<String, String> = HashMap::from([
-            ("namespace".to_string(), "passion".to_string()),
-            ("project_name".to_string(), "fruit".to_string()),
+        let defs: Definitions = HashMap::from([
+            ("namespace".to_string(), "passion".into()),
+            ("project_name".to_string(), "fruit".into()),
         ]);
 
-        let rendered = render_template(pt, &defs);
+        let rendered = render_template(pt, &defs).expect("Error rendering template");
 
         assert!(rendered.directories.contains(&PathBuf::from("docs")));
         assert!(rendered.directories.contains(&PathBuf::from("include")));
         assert!(rendered.directories.contains(&PathBuf::from("src")));
         assert!(rendered
             .files
-            .contains_key(&PathBuf::from(r"include\passion\fruit.hpp")));
+            .contains_key(&PathBuf::from("include/passion/fruit.hpp")));
+        assert!(
+            rendered.files[&PathBuf::from("include/passion/fruit.hpp")].content == b"passionfruit"
+        );
+    }
+
+    #[test]
+    fn empty_paths_skipped() {
+        let pt: ProjectTemplate = serde_yaml::from_str(
+            "
+            variables: [use_tests]
+            directories: ['{@ if use_tests @}tests{@ end @}']
+            files:
+            - path: '{@ if use_tests @}tests/test.cpp{@ end @}'
+              content: ''
+            - path: README.md
+              content: ''
+            ",
+        )
+        .expect("Error deserializing");
+        let defs = serde_yaml::from_str("use_tests: 'false'").expect("Error deserializing");
+
+        let rendered = render_template(pt, &defs).expect("Error rendering template");
+
+        assert!(rendered.directories.is_empty());
+        assert!(rendered.files.keys().collect::<Vec<_>>() == vec![&PathBuf::from("README.md")]);
+    }
+
+    #[test]
+    fn if_else() {
+        let templ = "project(x)\n{@ if use_tests @}\nadd_subdirectory(tests)\n{@ else @}\n# no tests\n{@ end @}\ninstall()\n";
+
+        let on = HashMap::from([("use_tests".to_string(), "true".into())]);
+        let off = HashMap::from([("use_tests".to_string(), "false".into())]);
+
+        assert!(
+            render_string(templ.to_string(), &on).unwrap()
+                == "project(x)\nadd_subdirectory(tests)\ninstall()\n"
+        );
+        assert!(
+            render_string(templ.to_string(), &off).unwrap()
+                == "project(x)\n# no tests\ninstall()\n"
+        );
         assert!(
-            rendered.files[&PathBuf::from(r"include\passion\fruit.hpp")]
-                == "passionfruit".to_string()
+            render_string(templ.to_string(), &HashMap::new()).unwrap()
+                == "project(x)\n# no tests\ninstall()\n"
         );
     }
+
+    #[test]
+    fn nested_inline_if() {
+        let defs = HashMap::from([
+            ("a".to_string(), "yes".into()),
+            ("b".to_string(), "".into()),
+            ("name".to_string(), "fruit".into()),
+        ]);
+
+        let actual = render_string(
+            "[{@ if a @}A{@ if b @}B{@ else @}{@ name @}{@ end @}{@ end @}]".to_string(),
+            &defs,
+        )
+        .unwrap();
+
+        assert!(actual == "[Afruit]");
+    }
+
+    #[test]
+    fn unbalanced_blocks() {
+        let defs = HashMap::new();
+
+        assert!(render_string("{@ if a @}text".to_string(), &defs).is_err());
+        assert!(render_string("text{@ end @}".to_string(), &defs).is_err());
+        assert!(render_string("{@ else @}".to_string(), &defs).is_err());
+        assert!(
+            render_string("{@ if a @}{@ else @}{@ else @}{@ end @}".to_string(), &defs).is_err()
+        );
+    }
+
+    #[test]
+    fn undefined_var_left_in_place() {
+        let actual = render_string("{@ namespace  @}".to_string(), &HashMap::new()).unwrap();
+
+        assert!(actual == "{@ namespace  @}");
+    }
+
+    #[test]
+    fn for_loop() {
+        let defs = HashMap::from([(
+            "modules".to_string(),
+            Value::List(vec!["core".to_string(), "io".to_string()]),
+        )]);
+
+        let actual = render_string(
+            "{@ for m in modules @}\nadd_subdirectory({@ m @})\n{@ end @}".to_string(),
+            &defs,
+        )
+        .unwrap();
+
+        assert!(actual == "add_subdirectory(core)\nadd_subdirectory(io)\n");
+    }
+
+    #[test]
+    fn for_loop_path_expansion() {
+        let pt = ProjectTemplate {
+            variables: vec![Variable::from("modules")],
+            directories: vec!["{@ for m in modules @}src/{@ m @}{@ end @}".to_string()],
+            files: vec![TemplateFile {
+                path: "{@ for m in modules @}src/{@ m @}/{@ m @}.cpp{@ end @}".to_string(),
+                content: "// {@ m @}".to_string(),
+                encoding: None,
+                mode: None,
+            }],
+            ..ProjectTemplate::default()
+        };
+
+        let defs = HashMap::from([(
+            "modules".to_string(),
+            Value::List(vec!["core".to_string(), "io".to_string()]),
+        )]);
+
+        let rendered = render_template(pt, &defs).expect("Error rendering template");
+
+        assert!(rendered.directories == vec![PathBuf::from("src/core"), PathBuf::from("src/io")]);
+        assert!(rendered.files.len() == 2);
+        assert!(rendered.files[&PathBuf::from("src/core/core.cpp")].content == b"// core");
+        assert!(rendered.files[&PathBuf::from("src/io/io.cpp")].content == b"// io");
+    }
+
+    #[test]
+    fn deserialize_values() {
+        let defs: Definitions =
+            serde_yaml::from_str("name: fruit\nuse_tests: true\nmodules: [core, io]\n")
+                .expect("Error deserializing");
+
+        assert!(defs["name"] == Value::from("fruit"));
+        assert!(defs["use_tests"] == Value::from("true"));
+        assert!(defs["modules"] == Value::List(vec!["core".to_string(), "io".to_string()]));
+    }
+
+    #[test]
+    fn filters() {
+        let defs = HashMap::from([("project_name".to_string(), "my project".into())]);
+
+        let actual = render_string(
+            "#ifndef {@ project_name | snake | upper @}_H\nnamespace {@project_name|snake@} {\nclass {@ project_name | pascal @};\n}"
+                .to_string(),
+            &defs,
+        )
+        .unwrap();
+
+        assert!(actual == "#ifndef MY_PROJECT_H\nnamespace my_project {\nclass MyProject;\n}");
+    }
+
+    #[test]
+    fn unknown_filter() {
+        assert!(render_string("{@ name | shout @}".to_string(), &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn defaults() {
+        let vars = vec![
+            Variable::from("project_name"),
+            Variable {
+                default: Some("{@ project_name | snake @}".into()),
+                ..Variable::from("namespace")
+            },
+            Variable {
+                default: Some("unused".into()),
+                ..Variable::from("license")
+            },
+        ];
+        let mut defs = HashMap::from([
+            ("project_name".to_string(), "My Project".into()),
+            ("license".to_string(), "MIT".into()),
+        ]);
+
+        apply_defaults(&vars, &mut defs).expect("Error applying defaults");
+
+        assert!(defs["namespace"] == Value::from("my_project"));
+        assert!(defs["license"] == Value::from("MIT"));
+    }
+
+    #[test]
+    fn defaults_follow_references() {
+        let vars = vec![
+            Variable {
+                default: Some("{@ namespace @}_tests".into()),
+                ..Variable::from("test_namespace")
+            },
+            Variable {
+                default: Some("{@ project_name | snake @}".into()),
+                ..Variable::from("namespace")
+            },
+            Variable::from("project_name"),
+        ];
+        let mut defs = HashMap::from([("project_name".to_string(), "My Project".into())]);
+
+        let order: Vec<&str> = default_order(&vars)
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+        assert!(order == ["project_name", "namespace", "test_namespace"]);
+
+        apply_defaults(&vars, &mut defs).expect("Error applying defaults");
+
+        assert!(defs["test_namespace"] == Value::from("my_project_tests"));
+
+        // Defaults waiting on a variable without a value are left for prompting
+        let mut defs = HashMap::new();
+        apply_defaults(&vars, &mut defs).expect("Error applying defaults");
+
+        assert!(defs.is_empty());
+    }
+
+    #[test]
+    fn unresolved_default() {
+        let var = Variable {
+            default: Some("{@ project_name @}".into()),
+            ..Variable::from("namespace")
+        };
+
+        assert!(matches!(
+            render_default(&var, &HashMap::new()),
+            Err(RenderError::UnresolvedDefault(v, r)) if v == "namespace" && r == "project_name"
+        ));
+
+        // Undeclared references can never be given a value
+        let mut defs = HashMap::new();
+        assert!(apply_defaults(&[var], &mut defs).is_err());
+    }
+
+    #[test]
+    fn binary_file_copied() {
+        let pt = ProjectTemplate {
+            variables: vec![Variable::from("project_name")],
+            directories: vec![],
+            files: vec![TemplateFile {
+                path: "{@ project_name @}.bin".to_string(),
+                // "{@ project_name @}\0"
+                content: "e0AgcHJvamVjdF9uYW1lIEB9AA==".to_string(),
+                encoding: Some(FileEncoding::Base64),
+                mode: None,
+            }],
+            ..ProjectTemplate::default()
+        };
+        let defs = HashMap::from([("project_name".to_string(), "fruit".into())]);
+
+        let rendered = render_template(pt, &defs).expect("Error rendering template");
+
+        assert!(rendered.files[&PathBuf::from("fruit.bin")].content == b"{@ project_name @}\0");
+    }
 }
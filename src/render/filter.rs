@@ -0,0 +1,109 @@
+use super::RenderError;
+use heck::{ToKebabCase, ToLowerCamelCase, ToSnakeCase, ToTitleCase, ToUpperCamelCase};
+use regex::Regex;
+
+/// Transform applied to a variable's value, written as `{@ var | filter @}`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Filter {
+    Upper,
+    Lower,
+    Snake,
+    Camel,
+    Pascal,
+    Kebab,
+    Title,
+    Replace(String, String),
+}
+
+impl Filter {
+    pub fn apply(&self, value: &str) -> String {
+        return match self {
+            Filter::Upper => value.to_uppercase(),
+            Filter::Lower => value.to_lowercase(),
+            Filter::Snake => value.to_snake_case(),
+            Filter::Camel => value.to_lower_camel_case(),
+            Filter::Pascal => value.to_upper_camel_case(),
+            Filter::Kebab => value.to_kebab_case(),
+            Filter::Title => value.to_title_case(),
+            Filter::Replace(from, to) => value.replace(from.as_str(), to),
+        };
+    }
+}
+
+/// Parse a chain of filters such as `| snake | replace("_", "-")`.
+pub fn parse_filters(src: &str) -> Result<Vec<Filter>, RenderError> {
+    let filter_re = Regex::new(r#"^\s*\|\s*(?P<name>\w+)\s*(?:\((?P<args>[^)]*)\))?\s*"#)
+        .expect("Error compiling regex");
+    let arg_re = Regex::new(r#""(?P<arg>[^"]*)""#).expect("Error compiling regex");
+    let mut filters = Vec::new();
+    let mut rest = src;
+
+    while !rest.trim().is_empty() {
+        let Some(c) = filter_re.captures(rest) else {
+            return Err(RenderError::InvalidFilter(src.trim().to_string()));
+        };
+
+        let name = &c["name"];
+        let args = c.name("args").map_or(Vec::new(), |a| {
+            arg_re
+                .captures_iter(a.as_str())
+                .map(|a| a["arg"].to_string())
+                .collect()
+        });
+
+        let filter = match (name, args.as_slice()) {
+            ("upper", []) => Filter::Upper,
+            ("lower", []) => Filter::Lower,
+            ("snake", []) => Filter::Snake,
+            ("camel", []) => Filter::Camel,
+            ("pascal", []) => Filter::Pascal,
+            ("kebab", []) => Filter::Kebab,
+            ("title", []) => Filter::Title,
+            ("replace", [from, to]) => Filter::Replace(from.to_string(), to.to_string()),
+            _ => return Err(RenderError::InvalidFilter(name.to_string())),
+        };
+
+        filters.push(filter);
+        rest = &rest[c.get(0).expect("Capture without match").end()..];
+    }
+
+    return Ok(filters);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_filters, Filter};
+
+    #[test]
+    fn parse() {
+        let filters = parse_filters(r#"| snake |upper| replace("_", "-")"#).expect("Error parsing");
+
+        assert!(
+            filters
+                == vec![
+                    Filter::Snake,
+                    Filter::Upper,
+                    Filter::Replace("_".to_string(), "-".to_string())
+                ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse_filters("| shout").is_err());
+        assert!(parse_filters(r#"| replace("_")"#).is_err());
+        assert!(parse_filters("upper").is_err());
+    }
+
+    #[test]
+    fn case_conversion() {
+        let name = "my project";
+
+        assert!(Filter::Upper.apply(name) == "MY PROJECT");
+        assert!(Filter::Snake.apply(name) == "my_project");
+        assert!(Filter::Camel.apply(name) == "myProject");
+        assert!(Filter::Pascal.apply(name) == "MyProject");
+        assert!(Filter::Kebab.apply(name) == "my-project");
+        assert!(Filter::Title.apply("my_project") == "My Project");
+    }
+}
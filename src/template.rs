@@ -1,32 +1,616 @@
+use crate::{
+    cmd::fd::get_template_vars,
+    git::{Checkout, GitError, GitSource},
+    library::{resolve_template, LibraryError},
+    paths::{read_directory, DirectoryError},
+    render::Value,
+};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, io::Read, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("Error opening template file")]
+    FileError(PathBuf),
+
+    #[error("Unable to parse template")]
+    ParseError(String),
+
+    #[error("Invalid version in template metadata")]
+    InvalidVersion(String),
+
+    #[error("Template needs a newer version of pgen")]
+    UnsupportedVersion(String),
+
+    #[error("Template extends or includes itself")]
+    CycleError(PathBuf),
+
+    #[error("Unable to read template directory")]
+    DirectoryReadError(#[source] DirectoryError),
+
+    #[error("Unable to fetch template from git")]
+    GitError(#[source] GitError),
+
+    #[error("Variable has a pattern that isn't a valid regex")]
+    InvalidPattern(String, String),
+
+    #[error("Unable to open template library")]
+    LibraryError(#[source] LibraryError),
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableType {
+    String,
+    List,
+    Bool,
+    Integer,
+}
+
+/// Variable declared by a template.
+///
+/// Can be written in yaml as just the variable's name, or as a map with the optional fields.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(from = "VariableEntry")]
+pub struct Variable {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Used when no definition is given, may reference other variables
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub var_type: Option<VariableType>,
+
+    /// Regex the whole value must match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+
+    /// Values the variable is allowed to take
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<String>>,
+
+    /// Smallest allowed value of an integer variable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<i64>,
+
+    /// Largest allowed value of an integer variable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum VariableEntry {
+    Name(String),
+    Full(VariableFields),
+}
+
+/// Fields of a variable written as a map, misspelled fields are rejected rather than ignored.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VariableFields {
+    name: String,
+    description: Option<String>,
+    default: Option<Value>,
+    #[serde(rename = "type")]
+    var_type: Option<VariableType>,
+    pattern: Option<String>,
+    choices: Option<Vec<String>>,
+    min: Option<i64>,
+    max: Option<i64>,
+}
+
+impl From<VariableEntry> for Variable {
+    fn from(entry: VariableEntry) -> Self {
+        return match entry {
+            VariableEntry::Name(name) => Variable::from(name.as_str()),
+            VariableEntry::Full(fields) => Variable {
+                name: fields.name,
+                description: fields.description,
+                default: fields.default,
+                var_type: fields.var_type,
+                pattern: fields.pattern,
+                choices: fields.choices,
+                min: fields.min,
+                max: fields.max,
+            },
+        };
+    }
+}
+
+impl From<&str> for Variable {
+    fn from(name: &str) -> Self {
+        return Variable {
+            name: name.to_string(),
+            description: None,
+            default: None,
+            var_type: None,
+            pattern: None,
+            choices: None,
+            min: None,
+            max: None,
+        };
+    }
+}
+
+fn is_bool(item: &str) -> bool {
+    return matches!(
+        item.trim().to_lowercase().as_str(),
+        "true" | "false" | "yes" | "no" | "on" | "off" | "1" | "0"
+    );
+}
+
+impl Variable {
+    /// Check that the variable's own rules are well formed, so bad ones are reported once when the
+    /// template is read rather than on every answer.
+    pub fn check(&self) -> Result<(), TemplateError> {
+        if let Some(pattern) = &self.pattern {
+            if pattern_regex(pattern).is_err() {
+                return Err(TemplateError::InvalidPattern(
+                    self.name.clone(),
+                    pattern.clone(),
+                ));
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Check `value` against the variable's type and constraints, returning the rule it breaks.
+    pub fn validate(&self, value: &Value) -> Result<(), String> {
+        let items = match (self.var_type, value) {
+            (Some(VariableType::List), Value::List(l)) => l.clone(),
+            (Some(VariableType::List), Value::Scalar(_)) => {
+                return Err("must be a list".to_string())
+            }
+            (Some(_), Value::List(_)) => return Err("must be a single value".to_string()),
+            (None, Value::List(l)) => l.clone(),
+            (_, Value::Scalar(s)) => vec![s.clone()],
+        };
+
+        for item in &items {
+            self.validate_item(item)?;
+        }
+
+        return Ok(());
+    }
+
+    fn validate_item(&self, item: &str) -> Result<(), String> {
+        match self.var_type {
+            Some(VariableType::Bool) if !is_bool(item) => {
+                return Err("must be a boolean".to_string());
+            }
+            Some(VariableType::Integer) => {
+                let Ok(n) = item.trim().parse::<i64>() else {
+                    return Err("must be an integer".to_string());
+                };
+
+                if let Some(min) = self.min {
+                    if n < min {
+                        return Err(format!("must be at least {}", min));
+                    }
+                }
+
+                if let Some(max) = self.max {
+                    if n > max {
+                        return Err(format!("must be at most {}", max));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(pattern) = &self.pattern {
+            let Ok(re) = pattern_regex(pattern) else {
+                return Err(format!("has an invalid pattern '{}'", pattern));
+            };
+
+            if !re.is_match(item) {
+                return Err(format!("must match pattern '{}'", pattern));
+            }
+        }
+
+        if let Some(choices) = &self.choices {
+            if !choices.iter().any(|c| c == item) {
+                return Err(format!("must be one of: {}", choices.join(", ")));
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Patterns have to match the whole value.
+fn pattern_regex(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    return regex::Regex::new(&format!("^(?:{})$", pattern));
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum FileEncoding {
+    Base64,
+}
+
+/// Unix permission bits of a file, written in yaml in octal such as "755".
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(try_from = "ModeEntry")]
+pub struct FileMode(pub u32);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ModeEntry {
+    Octal(String),
+    Digits(u32),
+}
+
+impl TryFrom<ModeEntry> for FileMode {
+    type Error = String;
+
+    fn try_from(entry: ModeEntry) -> Result<Self, Self::Error> {
+        let digits = match entry {
+            ModeEntry::Octal(s) => s,
+            ModeEntry::Digits(d) => d.to_string(),
+        };
+
+        return match u32::from_str_radix(digits.trim_start_matches("0o"), 8) {
+            Ok(m) if m <= 0o7777 => Ok(FileMode(m)),
+            _ => Err(format!("Invalid file mode '{}'", digits)),
+        };
+    }
+}
+
+impl Serialize for FileMode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(&format!("{:o}", self.0));
+    }
+}
 
 #[derive(Serialize, Deserialize, PartialEq, Eq)]
 pub struct TemplateFile {
     pub path: String,
     pub content: String,
+
+    /// Encoding of binary content, which is copied without substitution
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<FileEncoding>,
+    /// Permissions set on the generated file, only applied on unix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<FileMode>,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq)]
+/// Information about a template for the people using it.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Version of the template itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+
+    /// Oldest pgen release the template works with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_pgen_version: Option<String>,
+}
+
+impl TemplateMetadata {
+    /// Check that versions are well formed and that this pgen is new enough for the template.
+    pub fn check(&self) -> Result<(), TemplateError> {
+        if let Some(v) = &self.version {
+            if parse_version(v).is_none() {
+                return Err(TemplateError::InvalidVersion(v.clone()));
+            }
+        }
+
+        if let Some(min) = &self.min_pgen_version {
+            let Some(required) = parse_version(min) else {
+                return Err(TemplateError::InvalidVersion(min.clone()));
+            };
+            let current = parse_version(env!("CARGO_PKG_VERSION"));
+
+            if current.is_some_and(|c| required > c) {
+                return Err(TemplateError::UnsupportedVersion(min.clone()));
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Parse a version such as "1.2.3", missing minor or patch numbers are taken as 0.
+///
+/// Pre-release and build suffixes such as "-beta" or "+build.5" are allowed but not compared.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = match version.split_once(['-', '+']) {
+        Some((core, suffix)) if !suffix.is_empty() => core,
+        Some(_) => return None,
+        None => version,
+    };
+    let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    return Some((major, minor, patch));
+}
+
+/// Shell commands run around generation, rendered with the definitions first.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    /// Run before anything is written, in the directory pgen is run from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_gen: Vec<String>,
+
+    /// Run in the project root once everything is written
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_gen: Vec<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        return self.pre_gen.is_empty() && self.post_gen.is_empty();
+    }
+
+    /// Every command, pre_gen first.
+    pub fn commands(&self) -> impl Iterator<Item = &String> {
+        return self.pre_gen.iter().chain(&self.post_gen);
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct ProjectTemplate {
-    pub variables: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<TemplateMetadata>,
+
+    /// Template this one is based on, relative to this template's file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+
+    /// Templates whose files and variables are added to this one, relative to this template's file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    #[serde(default)]
+    pub variables: Vec<Variable>,
+    #[serde(default)]
     pub directories: Vec<String>,
+    #[serde(default)]
     pub files: Vec<TemplateFile>,
+
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+}
+
+impl ProjectTemplate {
+    /// Layer `other` over this template. Its variables and files replace the ones with the same
+    /// name or path, in the place they were declared, and anything new is added at the end. Its
+    /// hooks run after the ones already there.
+    fn merge(&mut self, other: ProjectTemplate) {
+        self.hooks.pre_gen.extend(other.hooks.pre_gen);
+        self.hooks.post_gen.extend(other.hooks.post_gen);
+
+        for var in other.variables {
+            match self.variables.iter_mut().find(|v| v.name == var.name) {
+                Some(v) => *v = var,
+                None => self.variables.push(var),
+            }
+        }
+
+        let same_dir = |a: &str, b: &str| {
+            return normalize_path(a).trim_end_matches('/')
+                == normalize_path(b).trim_end_matches('/');
+        };
+        for dir in other.directories {
+            if !self.directories.iter().any(|d| same_dir(d, &dir)) {
+                self.directories.push(dir);
+            }
+        }
+
+        for file in other.files {
+            match self
+                .files
+                .iter_mut()
+                .find(|f| normalize_path(&f.path) == normalize_path(&file.path))
+            {
+                Some(f) => *f = file,
+                None => self.files.push(file),
+            }
+        }
+    }
+}
+pub struct RenderedFile {
+    pub content: Vec<u8>,
+    pub mode: Option<FileMode>,
 }
+
 pub struct RenderedTemplate {
     pub directories: Vec<PathBuf>,
-    pub files: HashMap<PathBuf, String>,
+    pub files: HashMap<PathBuf, RenderedFile>,
+}
+
+/// Template paths always use `/` as the separator so templates work on every platform.
+pub fn normalize_path(path: &str) -> String {
+    return path.replace('\\', "/");
 }
 
-pub fn read_template(src: impl Read) -> Result<ProjectTemplate, impl std::error::Error> {
-    return serde_yaml::from_reader(src);
+/// Parse a single template, without resolving the templates it extends or includes.
+pub fn read_template(src: impl Read) -> Result<ProjectTemplate, TemplateError> {
+    let templ: ProjectTemplate = match serde_yaml::from_reader(src) {
+        Ok(t) => t,
+        Err(e) => return Err(TemplateError::ParseError(e.to_string())),
+    };
+
+    if let Some(metadata) = &templ.metadata {
+        metadata.check()?;
+    }
+
+    for var in &templ.variables {
+        var.check()?;
+    }
+
+    return Ok(templ);
+}
+
+/// Manifest of a template directory.
+pub const MANIFEST_NAME: &str = "pgen.yaml";
+
+/// Read a template laid out as a directory of real files.
+///
+/// Metadata, variables, `extends` and `include` come from the optional `pgen.yaml` manifest, and
+/// every other file and directory that isn't ignored is part of the template as it is on disk.
+/// Without a manifest, every variable the files use is declared like `fd` does.
+pub fn read_template_dir(dir: &Path) -> Result<ProjectTemplate, TemplateError> {
+    let manifest_path = dir.join(MANIFEST_NAME);
+    let manifest = match File::open(&manifest_path) {
+        Ok(f) => Some(read_template(f)?),
+        Err(_) if !manifest_path.exists() => None,
+        Err(_) => return Err(TemplateError::FileError(manifest_path)),
+    };
+
+    let exclude = [format!("/{}", MANIFEST_NAME)];
+    let (directories, files) = match read_directory(dir, &exclude, &[]) {
+        Ok(r) => r,
+        Err(e) => return Err(TemplateError::DirectoryReadError(e)),
+    };
+
+    let Some(mut templ) = manifest else {
+        let mut vars: Vec<String> = get_template_vars(&directories, &files)
+            .into_iter()
+            .collect();
+        vars.sort();
+
+        return Ok(ProjectTemplate {
+            variables: vars.iter().map(|v| Variable::from(v.as_str())).collect(),
+            directories,
+            files,
+            ..ProjectTemplate::default()
+        });
+    };
+
+    templ.merge(ProjectTemplate {
+        directories,
+        files,
+        ..ProjectTemplate::default()
+    });
+
+    return Ok(templ);
+}
+
+/// Read the template file or directory at `path`, merged with the templates it extends and
+/// includes. A `path` that doesn't exist is looked up by name in the template library.
+///
+/// The template it extends is applied first, then each include in order, then the template itself,
+/// so later ones override files and variables of earlier ones. Metadata is never inherited.
+pub fn load_template(path: &Path) -> Result<ProjectTemplate, TemplateError> {
+    return Ok(load_template_source(path)?.0);
+}
+
+/// Load a template like [`load_template`], where `source` can also be a git repository written as
+/// `url[//subpath][#ref]`. Returns the checkout as well when the template came from git.
+pub fn load_template_source(
+    source: &Path,
+) -> Result<(ProjectTemplate, Option<Checkout>), TemplateError> {
+    let Some(git_source) = source.to_str().and_then(GitSource::parse) else {
+        let path = match resolve_template(source) {
+            Ok(p) => p,
+            Err(e) => return Err(TemplateError::LibraryError(e)),
+        };
+        return Ok((load_with_parents(&path, &mut Vec::new())?, None));
+    };
+
+    let checkout = match git_source.checkout() {
+        Ok(c) => c,
+        Err(e) => return Err(TemplateError::GitError(e)),
+    };
+    let templ = load_with_parents(&checkout.path, &mut Vec::new())?;
+
+    return Ok((templ, Some(checkout)));
+}
+
+/// Load a template while `parents` holds the templates that led to it, to catch cycles.
+fn load_with_parents(
+    path: &Path,
+    parents: &mut Vec<PathBuf>,
+) -> Result<ProjectTemplate, TemplateError> {
+    let Ok(canon) = path.canonicalize() else {
+        return Err(TemplateError::FileError(path.to_path_buf()));
+    };
+    if parents.contains(&canon) {
+        return Err(TemplateError::CycleError(path.to_path_buf()));
+    }
+
+    // Paths in a template are relative to the directory holding its yaml
+    let (mut templ, dir) = if path.is_dir() {
+        (read_template_dir(path)?, path)
+    } else {
+        let Ok(templ_file) = File::open(path) else {
+            return Err(TemplateError::FileError(path.to_path_buf()));
+        };
+        (
+            read_template(templ_file)?,
+            path.parent().unwrap_or(Path::new("")),
+        )
+    };
+    let layers: Vec<String> = templ
+        .extends
+        .take()
+        .into_iter()
+        .chain(std::mem::take(&mut templ.include))
+        .collect();
+
+    if layers.is_empty() {
+        return Ok(templ);
+    }
+
+    parents.push(canon);
+    let mut merged = ProjectTemplate::default();
+    for layer in layers {
+        merged.merge(load_with_parents(
+            &dir.join(normalize_path(&layer)),
+            parents,
+        )?);
+    }
+    parents.pop();
+
+    merged.metadata = templ.metadata.take();
+    merged.merge(templ);
+
+    return Ok(merged);
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::template::{ProjectTemplate, TemplateFile};
+    use crate::{
+        render::Value,
+        template::{FileEncoding, FileMode, ProjectTemplate, TemplateFile, Variable, VariableType},
+    };
 
-    use super::read_template;
+    use super::{
+        load_template, normalize_path, parse_version, read_template, read_template_dir,
+        TemplateError,
+    };
 
     #[test]
     fn read() {
@@ -46,8 +630,8 @@ mod tests {
 
         let pt = read_template(yaml).expect("Error reading template");
 
-        assert!(pt.variables.iter().any(|s| s == "name"));
-        assert!(pt.variables.iter().any(|s| s == "age"));
+        assert!(pt.variables.iter().any(|v| v.name == "name"));
+        assert!(pt.variables.iter().any(|v| v.name == "age"));
 
         assert!(pt.directories.iter().any(|s| s == "src"));
         assert!(pt.directories.iter().any(|s| s == "include"));
@@ -56,6 +640,8 @@ mod tests {
         assert!(pt.files.contains(&TemplateFile {
             path: "src/main.cpp".to_string(),
             content: "hello world".to_string(),
+            encoding: None,
+            mode: None,
         }));
     }
 
@@ -76,8 +662,8 @@ mod tests {
 
         let pt: ProjectTemplate = serde_yaml::from_str(yaml).expect("Error deserializing");
 
-        assert!(pt.variables.iter().any(|s| s == "name"));
-        assert!(pt.variables.iter().any(|s| s == "age"));
+        assert!(pt.variables.iter().any(|v| v.name == "name"));
+        assert!(pt.variables.iter().any(|v| v.name == "age"));
 
         assert!(pt.directories.iter().any(|s| s == "src"));
         assert!(pt.directories.iter().any(|s| s == "include"));
@@ -86,6 +672,8 @@ mod tests {
         assert!(pt.files.contains(&TemplateFile {
             path: "src/main.cpp".to_string(),
             content: "hello world".to_string(),
+            encoding: None,
+            mode: None,
         }));
     }
 
@@ -113,6 +701,8 @@ mod tests {
         assert!(pt.files.contains(&TemplateFile {
             path: "src/main.cpp".to_string(),
             content: "hello world".to_string(),
+            encoding: None,
+            mode: None,
         }));
     }
 
@@ -130,14 +720,16 @@ mod tests {
 
         let pt: ProjectTemplate = serde_yaml::from_str(yaml).expect("Error deserializing");
 
-        assert!(pt.variables.iter().any(|s| s == "name"));
-        assert!(pt.variables.iter().any(|s| s == "age"));
+        assert!(pt.variables.iter().any(|v| v.name == "name"));
+        assert!(pt.variables.iter().any(|v| v.name == "age"));
 
         assert!(pt.directories.is_empty());
 
         assert!(pt.files.contains(&TemplateFile {
             path: "src/main.cpp".to_string(),
             content: "hello world".to_string(),
+            encoding: None,
+            mode: None,
         }));
     }
 
@@ -160,6 +752,8 @@ mod tests {
         assert!(pt.files.contains(&TemplateFile {
             path: "src/main.cpp".to_string(),
             content: "hello world".to_string(),
+            encoding: None,
+            mode: None,
         }));
     }
 
@@ -177,4 +771,363 @@ mod tests {
         assert!(pt.directories.is_empty());
         assert!(pt.files.is_empty());
     }
+
+    #[test]
+    fn deserialize_variable_forms() {
+        let yaml = r"
+        variables:
+            - project_name
+            - name: namespace
+              description: Namespace for the project's code
+              default: '{@ project_name | snake @}'
+            - name: modules
+              type: list
+              default: [core]
+        directories:
+        files:
+        ";
+
+        let pt: ProjectTemplate = serde_yaml::from_str(yaml).expect("Error deserializing");
+
+        assert!(pt.variables[0] == Variable::from("project_name"));
+        assert!(pt.variables[1].name == "namespace");
+        assert!(pt.variables[1].description.as_deref() == Some("Namespace for the project's code"));
+        assert!(pt.variables[1].default == Some(Value::from("{@ project_name | snake @}")));
+        assert!(pt.variables[2].var_type == Some(VariableType::List));
+        assert!(pt.variables[2].default == Some(Value::List(vec!["core".to_string()])));
+    }
+
+    #[test]
+    fn deserialize_variable_unknown_field() {
+        let yaml = r"
+        variables:
+            - name: namespace
+              defualt: fruit
+        ";
+
+        assert!(serde_yaml::from_str::<ProjectTemplate>(yaml).is_err());
+    }
+
+    #[test]
+    fn serialize_variable() {
+        let yaml = serde_yaml::to_string(&vec![Variable::from("project_name")])
+            .expect("Error serializing");
+
+        assert!(yaml == "- name: project_name\n");
+    }
+
+    #[test]
+    fn validate_variable() {
+        let yaml = r"
+        - name: namespace
+          pattern: '[A-Za-z_][A-Za-z0-9_]*'
+        - name: cxx_standard
+          choices: ['17', '20', '23']
+        - name: use_tests
+          type: bool
+        - name: warning_level
+          type: integer
+          min: 0
+          max: 4
+        - name: modules
+          type: list
+          pattern: '[a-z]+'
+        ";
+
+        let vars: Vec<Variable> = serde_yaml::from_str(yaml).expect("Error deserializing");
+
+        assert!(vars[0].validate(&Value::from("passion")).is_ok());
+        assert!(vars[0].validate(&Value::from("passion fruit")).is_err());
+        assert!(vars[1].validate(&Value::from("20")).is_ok());
+        assert!(vars[1].validate(&Value::from("11")).is_err());
+        assert!(vars[2].validate(&Value::from("true")).is_ok());
+        assert!(vars[2].validate(&Value::from("maybe")).is_err());
+        assert!(vars[3].validate(&Value::from("4")).is_ok());
+        assert!(vars[3].validate(&Value::from("5")).is_err());
+        assert!(vars[3].validate(&Value::from("high")).is_err());
+        assert!(vars[4]
+            .validate(&Value::List(vec!["core".to_string(), "io".to_string()]))
+            .is_ok());
+        assert!(vars[4]
+            .validate(&Value::List(vec!["Core".to_string()]))
+            .is_err());
+        assert!(vars[4].validate(&Value::from("core")).is_err());
+        assert!(Variable::from("any")
+            .validate(&Value::List(vec!["core".to_string()]))
+            .is_ok());
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let yaml = r"
+        variables:
+            - name: namespace
+              pattern: '[a-z'
+        ";
+
+        assert!(matches!(
+            read_template(yaml.as_bytes()),
+            Err(TemplateError::InvalidPattern(v, p)) if v == "namespace" && p == "[a-z"
+        ));
+    }
+
+    #[test]
+    fn deserialize_binary_file() {
+        let yaml = r"
+        variables:
+        directories:
+        files:
+            - path: icon.png
+              content: iVBORw0K
+              encoding: base64
+        ";
+
+        let pt: ProjectTemplate = serde_yaml::from_str(yaml).expect("Error deserializing");
+
+        assert!(pt.files[0].encoding == Some(FileEncoding::Base64));
+    }
+
+    #[test]
+    fn file_mode() {
+        let yaml = r"
+        - path: scripts/bootstrap.sh
+          content: ''
+          mode: '755'
+        - path: scripts/hook
+          content: ''
+          mode: 750
+        ";
+
+        let files: Vec<TemplateFile> = serde_yaml::from_str(yaml).expect("Error deserializing");
+
+        assert!(files[0].mode == Some(FileMode(0o755)));
+        assert!(files[1].mode == Some(FileMode(0o750)));
+        assert!(serde_yaml::from_str::<FileMode>("'789'").is_err());
+        assert!(serde_yaml::to_string(&FileMode(0o755)).unwrap() == "'755'\n");
+    }
+
+    #[test]
+    fn normalize() {
+        assert!(
+            normalize_path(r"include\{@ project_name @}\a.hpp")
+                == "include/{@ project_name @}/a.hpp"
+        );
+    }
+
+    #[test]
+    fn read_metadata() {
+        let yaml = r"
+        metadata:
+            name: cpp-lib
+            version: 1.2.0
+            authors:
+                - Ana
+            min_pgen_version: 0.1.0
+        variables:
+        directories:
+        files:
+        "
+        .as_bytes();
+
+        let pt = read_template(yaml).expect("Error reading template");
+        let metadata = pt.metadata.expect("Missing metadata");
+
+        assert!(metadata.name.as_deref() == Some("cpp-lib"));
+        assert!(metadata.version.as_deref() == Some("1.2.0"));
+        assert!(metadata.authors == vec!["Ana".to_string()]);
+        assert!(metadata.description.is_none());
+    }
+
+    #[test]
+    fn metadata_checks() {
+        let newer = "metadata:\n  min_pgen_version: 999.0\nvariables:\ndirectories:\nfiles:\n";
+        assert!(matches!(
+            read_template(newer.as_bytes()),
+            Err(TemplateError::UnsupportedVersion(v)) if v == "999.0"
+        ));
+
+        let invalid = "metadata:\n  version: one\nvariables:\ndirectories:\nfiles:\n";
+        assert!(matches!(
+            read_template(invalid.as_bytes()),
+            Err(TemplateError::InvalidVersion(_))
+        ));
+
+        let unknown = "metadata:\n  min_version: 0.1\nvariables:\ndirectories:\nfiles:\n";
+        assert!(matches!(
+            read_template(unknown.as_bytes()),
+            Err(TemplateError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn versions() {
+        assert!(parse_version("1.2.3") == Some((1, 2, 3)));
+        assert!(parse_version("1.2") == Some((1, 2, 0)));
+        assert!(parse_version("2") == Some((2, 0, 0)));
+        assert!(parse_version("1.2.3.4").is_none());
+        assert!(parse_version("1.x").is_none());
+        assert!(parse_version("").is_none());
+        assert!(parse_version("1.0.0-beta") == Some((1, 0, 0)));
+        assert!(parse_version("0.2.0-rc.1+build.5") == Some((0, 2, 0)));
+        assert!(parse_version("1.0.0-").is_none());
+        assert!(parse_version("-beta").is_none());
+        assert!(parse_version("0.10.0") > parse_version("0.9.5"));
+    }
+
+    #[test]
+    fn load_extends_and_include() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        std::fs::create_dir(dir.path().join("shared")).expect("Error creating dir");
+        std::fs::write(
+            dir.path().join("shared/base.yaml"),
+            r"
+            metadata:
+                name: base
+            variables:
+                - project_name
+                - name: std
+                  default: '17'
+            directories: ['', src]
+            files:
+                - path: .gitignore
+                  content: build/
+                - path: src/main.cpp
+                  content: base
+            ",
+        )
+        .expect("Error writing template");
+        std::fs::write(
+            dir.path().join("shared/ci.yaml"),
+            r"
+            directories: [.github]
+            files:
+                - path: .github/ci.yml
+                  content: ci
+            ",
+        )
+        .expect("Error writing template");
+        std::fs::write(
+            dir.path().join("lib.yaml"),
+            r"
+            extends: shared/base.yaml
+            include: [shared/ci.yaml]
+            variables:
+                - name: std
+                  default: '20'
+                - namespace
+            directories: [src/]
+            files:
+                - path: src\main.cpp
+                  content: lib
+            ",
+        )
+        .expect("Error writing template");
+
+        let pt = load_template(&dir.path().join("lib.yaml")).expect("Error loading template");
+
+        assert!(pt.metadata.is_none());
+        assert!(pt.extends.is_none() && pt.include.is_empty());
+        assert!(
+            pt.variables
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<&str>>()
+                == vec!["project_name", "std", "namespace"]
+        );
+        assert!(pt.variables[1].default == Some(Value::from("20")));
+        assert!(pt.directories == vec!["", "src", ".github"]);
+        assert!(
+            pt.files
+                .iter()
+                .map(|f| (f.path.as_str(), f.content.as_str()))
+                .collect::<Vec<(&str, &str)>>()
+                == vec![
+                    (".gitignore", "build/"),
+                    ("src\\main.cpp", "lib"),
+                    (".github/ci.yml", "ci"),
+                ]
+        );
+    }
+
+    #[test]
+    fn load_cycle() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        std::fs::write(dir.path().join("a.yaml"), "extends: b.yaml\n").expect("Error writing");
+        std::fs::write(dir.path().join("b.yaml"), "include: [a.yaml]\n").expect("Error writing");
+        std::fs::write(dir.path().join("c.yaml"), "extends: missing.yaml\n")
+            .expect("Error writing");
+
+        assert!(matches!(
+            load_template(&dir.path().join("a.yaml")),
+            Err(TemplateError::CycleError(_))
+        ));
+        assert!(matches!(
+            load_template(&dir.path().join("c.yaml")),
+            Err(TemplateError::FileError(p)) if p.ends_with("missing.yaml")
+        ));
+    }
+
+    #[test]
+    fn read_dir() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let root = dir.path().join("lib");
+        std::fs::create_dir_all(root.join("src")).expect("Error creating dir");
+        std::fs::write(
+            root.join("pgen.yaml"),
+            r"
+            metadata:
+                name: lib
+            extends: ../base.yaml
+            variables:
+                - project_name
+            ",
+        )
+        .expect("Error writing manifest");
+        std::fs::write(
+            root.join("src/main.cpp"),
+            "int main() {\n\treturn 0;\n}\n\n",
+        )
+        .expect("Error writing file");
+        std::fs::write(
+            dir.path().join("base.yaml"),
+            "files:\n  - path: .clang-format\n    content: 'BasedOnStyle: LLVM'\n",
+        )
+        .expect("Error writing template");
+
+        let pt = read_template_dir(&root).expect("Error reading template directory");
+
+        assert!(pt
+            .metadata
+            .is_some_and(|m| m.name.as_deref() == Some("lib")));
+        assert!(pt.extends.as_deref() == Some("../base.yaml"));
+        assert!(pt.directories == vec!["", "src"]);
+        assert!(pt.files.len() == 1);
+        assert!(pt.files[0].path == "src/main.cpp");
+        assert!(pt.files[0].content == "int main() {\n\treturn 0;\n}\n\n");
+
+        // Extends is resolved from the template directory
+        let pt = load_template(&root).expect("Error loading template");
+
+        assert!(pt.variables.len() == 1);
+        assert!(
+            pt.files
+                .iter()
+                .map(|f| f.path.as_str())
+                .collect::<Vec<&str>>()
+                == vec![".clang-format", "src/main.cpp"]
+        );
+    }
+
+    #[test]
+    fn read_dir_without_manifest() {
+        let pt = read_template_dir(std::path::Path::new("tests/templ-dir"))
+            .expect("Error reading template directory");
+
+        assert!(pt.metadata.is_none());
+        assert!(pt.variables == vec![Variable::from("namespace"), Variable::from("project_name")]);
+        assert!(pt
+            .directories
+            .contains(&"include/{@ project_name @}".to_string()));
+        assert!(pt.files.iter().any(|f| f.path == "src/main.cpp"));
+    }
 }